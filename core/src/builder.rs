@@ -0,0 +1,337 @@
+pub mod builder {
+    use proc_macro2::TokenStream;
+    use quote::{quote, ToTokens};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use syn::{parse::{Parse, ParseStream}, spanned::Spanned, parse_quote, Attribute, Ident, Item, TraitItem, Visibility};
+    use crate::errors::errors::Error;
+    use crate::trait_node::trait_node::{module_ident, TraitNode};
+    use crate::type_context::type_context::{is_flags, IdlFlags, TypeContext};
+
+    // an IDL file is a Rust file that may also declare `#[idl(flags)]` types
+    struct IdlFile {
+        items: Vec<IdlItem>,
+    }
+
+    enum IdlItem {
+        Item(Item),
+        Flags(IdlFlags),
+    }
+
+    impl Parse for IdlFile {
+        fn parse(input: ParseStream) -> syn::Result<Self> {
+            input.call(Attribute::parse_inner)?;
+            let mut items = vec![];
+            while !input.is_empty() {
+                let attrs = input.fork().call(Attribute::parse_outer)?;
+                items.push(match is_flags(&attrs) {
+                    true => IdlItem::Flags(input.parse()?),
+                    false => IdlItem::Item(input.parse()?),
+                });
+            }
+            Ok(IdlFile { items })
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+    pub enum Verbosity {
+        Quiet,
+        // "Parsing module" and unsupported method warnings
+        Normal,
+        // additionally every "Method encountered"
+        Verbose,
+    }
+
+    // Entry point for running the generator from a build script, e.g.
+    //
+    //     smos_idl::Builder::new()
+    //         .input("idl/conn.rs")
+    //         .out_dir(env!("OUT_DIR"))
+    //         .client(true)
+    //         .server(false)
+    //         .generate()?;
+    //
+    // writes $OUT_DIR/conn.rs for every input.
+    pub struct Builder {
+        inputs: Vec<PathBuf>,
+        out_dir: Option<PathBuf>,
+        client: bool,
+        server: bool,
+        mock: bool,
+        verbosity: Verbosity,
+        handle_types: Option<Vec<String>>,
+    }
+
+    impl Default for Builder {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Builder {
+        pub fn new() -> Self {
+            Builder {
+                inputs: vec![],
+                out_dir: None,
+                client: true,
+                server: false,
+                mock: false,
+                verbosity: Verbosity::Quiet,
+                handle_types: None,
+            }
+        }
+
+        pub fn input(mut self, path: impl AsRef<Path>) -> Self {
+            self.inputs.push(path.as_ref().to_path_buf());
+            self
+        }
+
+        // defaults to $OUT_DIR when not set
+        pub fn out_dir(mut self, path: impl AsRef<Path>) -> Self {
+            self.out_dir = Some(path.as_ref().to_path_buf());
+            self
+        }
+
+        pub fn client(mut self, client: bool) -> Self {
+            self.client = client;
+            self
+        }
+
+        pub fn server(mut self, server: bool) -> Self {
+            self.server = server;
+            self
+        }
+
+        pub fn mock(mut self, mock: bool) -> Self {
+            self.mock = mock;
+            self
+        }
+
+        // progress is logged to stderr so stdout stays free for generated code
+        pub fn verbosity(mut self, verbosity: Verbosity) -> Self {
+            self.verbosity = verbosity;
+            self
+        }
+
+        // restricts `LocalHandle<T>` to these `T`s, any are accepted otherwise
+        pub fn handle_types<I, S>(mut self, handle_types: I) -> Self
+        where
+            I: IntoIterator<Item = S>,
+            S: Into<String>,
+        {
+            self.handle_types = Some(handle_types.into_iter().map(Into::into).collect());
+            self
+        }
+
+        pub fn generate(self) -> Result<(), Error> {
+            let out_dir = match self.out_dir.clone() {
+                Some(out_dir) => out_dir,
+                None => std::env::var_os("OUT_DIR")
+                    .map(PathBuf::from)
+                    .ok_or(Error::NoOutDir)?
+            };
+            for input in &self.inputs {
+                let code = self.generate_file(input)?;
+                let file_name = input.file_name().ok_or_else(|| Error::NoFileName(input.clone()))?;
+                fs::write(out_dir.join(file_name), code).map_err(|e| Error::Io(out_dir.join(file_name), e))?;
+                if std::env::var_os("CARGO").is_some() {
+                    println!("cargo:rerun-if-changed={}", input.display());
+                }
+            }
+            Ok(())
+        }
+
+        // runs the whole pipeline over one IDL file and returns the formatted code
+        pub fn generate_file(&self, input: &Path) -> Result<String, Error> {
+            self.generate_files(&[input.to_path_buf()])
+        }
+
+        // generates several IDL files into one output sharing a single header,
+        // reporting the errors of every file rather than just the first. with
+        // more than one input each file goes in a module named after it, so
+        // the `use`s and definitions of different files can't collide
+        pub fn generate_files(&self, inputs: &[PathBuf]) -> Result<String, Error> {
+            let mut implementations = vec![];
+            let mut modules: Vec<Ident> = vec![];
+            let mut errors = vec![];
+            for input in inputs {
+                let content = fs::read_to_string(input).map_err(|e| Error::Io(input.to_path_buf(), e))?;
+                let items = match self.generate_items(input, &content) {
+                    Ok(items) => items,
+                    Err(e) => {
+                        errors.push(Error::InFile(input.to_path_buf(), content, Box::new(e)));
+                        continue;
+                    }
+                };
+                if inputs.len() == 1 {
+                    implementations.extend(items);
+                    continue;
+                }
+                let module = input_module(input)?;
+                if modules.contains(&module) {
+                    errors.push(Error::DuplicateModule(module.to_string(), input.to_path_buf()));
+                    continue;
+                }
+                implementations.push(quote! {
+                    pub mod #module {
+                        use super::*;
+
+                        #(#items)*
+                    }
+                });
+                modules.push(module);
+            }
+            if let Some(e) = Error::combine(errors) {
+                return Err(e);
+            }
+            format_rust_code(implementations)
+        }
+
+        pub fn generate_str(&self, content: &str) -> Result<String, Error> {
+            let path = Path::new("<input>");
+            let implementations = self
+                .generate_items(path, content)
+                .map_err(|e| Error::InFile(path.to_path_buf(), content.to_string(), Box::new(e)))?;
+            format_rust_code(implementations)
+        }
+
+        fn generate_items(&self, path: &Path, content: &str) -> Result<Vec<TokenStream>, Error> {
+            let ast: IdlFile = syn::parse_str(content).map_err(Error::Parse)?;
+
+            let mut implementations = vec![];
+            let mut errors = vec![];
+
+            // type declarations apply to the whole file, wherever they are declared
+            let mut context = TypeContext::new(self.handle_types.clone());
+            for item in &ast.items {
+                match item {
+                    IdlItem::Item(Item::Type(alias)) => context.add_alias(alias),
+                    IdlItem::Item(Item::Struct(item_struct)) => context.add_struct(item_struct),
+                    IdlItem::Item(Item::Enum(item_enum)) => context.add_enum(item_enum),
+                    IdlItem::Flags(flags) => context.add_flags(flags),
+                    _ => {}
+                }
+            }
+
+            for item in &ast.items {
+                let item = match item {
+                    IdlItem::Item(item) => item,
+                    IdlItem::Flags(flags) => {
+                        implementations.push(flags.to_definition());
+                        continue;
+                    }
+                };
+                match item {
+                    Item::Trait(item_trait) => {
+                        match self.generate_trait(item_trait.clone(), &context, path, content) {
+                            Ok(implementation) => implementations.push(implementation),
+                            Err(e) => errors.push(e),
+                        }
+                    },
+                    // C-like enums get their discriminants checked on the way in
+                    Item::Enum(item_enum) => {
+                        implementations.push(item.to_token_stream());
+                        let ident = &item_enum.ident;
+                        let ty: syn::Type = syn::parse_quote! { #ident };
+                        if let Some(idl_enum) = context.get_enum(&ty).filter(|e| e.is_c_like()) {
+                            implementations.push(idl_enum.to_try_from());
+                        }
+                    },
+                    // shared definitions the interfaces refer to are kept as they are
+                    Item::Type(_) | Item::Const(_) | Item::Struct(_) | Item::Use(_) => {
+                        implementations.push(item.to_token_stream());
+                    },
+                    _ => {
+                        errors.push(Error::UnsupportedItem(item.to_token_stream().to_string(), item.span()));
+                    }
+                }
+            }
+
+            match Error::combine(errors) {
+                Some(e) => Err(e),
+                None => Ok(implementations)
+            }
+        }
+
+        // each interface gets its own module so stubs of different traits can't collide
+        fn generate_trait(&self, mut item_trait: syn::ItemTrait, context: &TypeContext, path: &Path, content: &str) -> Result<TokenStream, Error> {
+            if self.verbosity >= Verbosity::Normal {
+                eprintln!("Parsing module {}", item_trait.ident);
+            }
+            if self.verbosity >= Verbosity::Verbose {
+                for item in &item_trait.items {
+                    if let TraitItem::Fn(method) = item {
+                        eprintln!("Method encountered {}", method.sig.ident);
+                    }
+                }
+            }
+            // a private trait would be private to its own module, keep it reachable from where it was declared
+            if let Visibility::Inherited = item_trait.vis {
+                item_trait.vis = parse_quote! { pub(super) };
+            }
+            let trait_node = TraitNode::new(item_trait, context)?;
+            if self.verbosity >= Verbosity::Normal {
+                for e in trait_node.errors() {
+                    eprint!("{}", e.report_warning(path, content, "falling back to an unimplemented stub"));
+                }
+            }
+            let mut implementations = vec![trait_node.to_labels()];
+            // the mock implements the trait, so needs it even without a client
+            if self.client || self.mock {
+                implementations.push(trait_node.to_trait(self.mock));
+            }
+            if self.client {
+                implementations.push(trait_node.to_client()?);
+            }
+            if self.server {
+                implementations.push(trait_node.to_server());
+            }
+            if self.mock {
+                implementations.push(trait_node.to_mock());
+            }
+            let module_ident = trait_node.module_ident();
+            Ok(quote! {
+                pub mod #module_ident {
+                    use super::*;
+
+                    #(#implementations)*
+                }
+            })
+        }
+    }
+
+    // `idl/file-system.rs` -> `file_system`
+    fn input_module(input: &Path) -> Result<Ident, Error> {
+        let stem = input
+            .file_stem()
+            .ok_or_else(|| Error::NoFileName(input.to_path_buf()))?
+            .to_string_lossy();
+        let mut name: String = stem.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            name.insert(0, '_');
+        }
+        Ok(module_ident(&name))
+    }
+
+    const HEADER: &str = "// Generated by smos_idl, do not edit.\n\n";
+
+    // names an IDL file may use unqualified, the generated code itself uses full paths
+    fn imports() -> TokenStream {
+        quote! {
+            #[allow(unused_imports)]
+            use smos_common::connection::ClientConnection;
+            #[allow(unused_imports)]
+            use smos_common::error::InvocationError;
+        }
+    }
+
+    fn format_rust_code(implementations: Vec<TokenStream>) -> Result<String, Error> {
+        let imports = imports();
+        let code = quote! {
+            #imports
+            #(#implementations)*
+        };
+        let syntax_tree: syn::File = syn::parse2(code).map_err(Error::Parse)?;
+        Ok(format!("{}{}", HEADER, prettyplease::unparse(&syntax_tree)))
+    }
+}
@@ -0,0 +1,210 @@
+pub mod errors {
+    use proc_macro2::{Span, TokenStream};
+    use std::path::{Path, PathBuf};
+
+    #[derive(Debug)]
+    pub enum Error {
+        UnsupportedType(String, Span),
+        UnsupportedReturnType(String, Span),
+        NoRecvSlot(String, Span),
+        MisplacedAttribute(String, Span),
+        ConflictingDirections(String, Span),
+        MultipleRecvSlots(String, Span),
+        TooManyCaps(String, usize, Span),
+        UnknownHandleType(String, Span),
+        NonIdentPattern(String, Span),
+        ReservedName(String, Span),
+        TooManyRegisters(String, usize, Span),
+        TooManyOptionals(String, Span),
+        InvalidLabel(String, Span),
+        NoNextLabel(String, Span),
+        DuplicateLabel(String, u64, Span),
+        DuplicateVariant(String, String, Span),
+        UnsupportedTraitItem(String, Span),
+        NoClientType(String, Span),
+        UnsupportedItem(String, Span),
+        Parse(syn::Error),
+        // every error found in one IDL file, kept with its source for rendering
+        InFile(PathBuf, String, Box<Error>),
+        Multiple(Vec<Error>),
+        Io(PathBuf, std::io::Error),
+        NoFileName(PathBuf),
+        DuplicateModule(String, PathBuf),
+        NoOutDir,
+    }
+
+    impl Error {
+        // None if there were no errors, otherwise one error holding all of them
+        pub fn combine(errors: Vec<Error>) -> Option<Error> {
+            let mut flat = vec![];
+            for e in errors {
+                match e {
+                    Error::Multiple(inner) => flat.extend(inner),
+                    e => flat.push(e),
+                }
+            }
+            match flat.len() {
+                0 => None,
+                1 => flat.pop(),
+                _ => Some(Error::Multiple(flat)),
+            }
+        }
+
+        pub fn span(&self) -> Span {
+            match self {
+                Error::UnsupportedType(_, span) => *span,
+                Error::UnsupportedReturnType(_, span) => *span,
+                Error::NoRecvSlot(_, span) => *span,
+                Error::MisplacedAttribute(_, span) => *span,
+                Error::ConflictingDirections(_, span) => *span,
+                Error::MultipleRecvSlots(_, span) => *span,
+                Error::TooManyCaps(_, _, span) => *span,
+                Error::UnknownHandleType(_, span) => *span,
+                Error::NonIdentPattern(_, span) => *span,
+                Error::ReservedName(_, span) => *span,
+                Error::TooManyRegisters(_, _, span) => *span,
+                Error::TooManyOptionals(_, span) => *span,
+                Error::InvalidLabel(_, span) => *span,
+                Error::NoNextLabel(_, span) => *span,
+                Error::DuplicateLabel(_, _, span) => *span,
+                Error::DuplicateVariant(_, _, span) => *span,
+                Error::UnsupportedTraitItem(_, span) => *span,
+                Error::NoClientType(_, span) => *span,
+                Error::UnsupportedItem(_, span) => *span,
+                Error::Parse(e) => e.span(),
+                Error::InFile(_, _, e) => e.span(),
+                Error::Multiple(errors) => errors.first().map_or(Span::call_site(), |e| e.span()),
+                Error::Io(..) | Error::NoFileName(_) | Error::DuplicateModule(..) | Error::NoOutDir => Span::call_site(),
+            }
+        }
+
+        pub fn to_compile_error(&self) -> TokenStream {
+            match self {
+                Error::Parse(e) => e.to_compile_error(),
+                Error::InFile(_, _, e) => e.to_compile_error(),
+                Error::Multiple(errors) => errors.iter().map(|e| e.to_compile_error()).collect(),
+                _ => syn::Error::new(self.span(), self.to_string()).to_compile_error(),
+            }
+        }
+
+        // rustc style report, ending in a newline
+        pub fn report(&self) -> String {
+            let mut out = self.render("error", None);
+            if self.count() > 1 {
+                out.push_str(&format!("error: aborting due to {} previous errors\n", self.count()));
+            }
+            out
+        }
+
+        fn count(&self) -> usize {
+            match self {
+                Error::InFile(_, _, e) => e.count(),
+                Error::Multiple(errors) => errors.iter().map(|e| e.count()).sum(),
+                Error::Parse(e) => e.clone().into_iter().count(),
+                _ => 1,
+            }
+        }
+
+        // like report, but for an error that didn't stop generation
+        pub fn report_warning(&self, path: &Path, source: &str, note: &str) -> String {
+            let mut out = self.render("warning", Some((path, source)));
+            out.insert_str(out.len() - 1, &format!("  = note: {}\n", note));
+            out
+        }
+
+        fn render(&self, level: &str, file: Option<(&Path, &str)>) -> String {
+            match self {
+                Error::InFile(path, source, e) => e.render(level, Some((path, source))),
+                Error::Multiple(errors) => errors.iter().map(|e| e.render(level, file)).collect(),
+                // a syn error can carry several messages of its own
+                Error::Parse(e) if e.clone().into_iter().count() > 1 => {
+                    e.clone().into_iter().map(|e| Error::Parse(e).render(level, file)).collect()
+                },
+                _ => {
+                    let mut out = format!("{}: {}\n", level, self);
+                    if let Some((path, source)) = file {
+                        out.push_str(&snippet(self.span(), path, source));
+                    }
+                    out.push('\n');
+                    out
+                }
+            }
+        }
+    }
+
+    // `Option<Option<u8>>` rather than the token stream's `Option < Option < u8 > >`,
+    // anything that doesn't read as a type on one line is left as it is
+    fn type_text(tokens: &str) -> String {
+        let ty: syn::Type = match syn::parse_str(tokens) {
+            Ok(ty) => ty,
+            Err(_) => return tokens.to_string(),
+        };
+        let file: syn::File = syn::parse_quote! { type T = #ty; };
+        match prettyplease::unparse(&file).trim().strip_prefix("type T = ").and_then(|text| text.strip_suffix(';')) {
+            Some(text) if !text.contains('\n') => text.to_string(),
+            _ => tokens.to_string(),
+        }
+    }
+
+    //  --> idl/conn.rs:3:22
+    //   |
+    // 3 |     fn set(a: foo);
+    //   |               ^^^
+    fn snippet(span: Span, path: &Path, source: &str) -> String {
+        let start = span.start();
+        let end = span.end();
+        let line = match source.lines().nth(start.line.wrapping_sub(1)) {
+            Some(line) if start.line > 0 => line,
+            _ => return format!(" --> {}\n", path.display()),
+        };
+        let underline_end = if end.line == start.line { end.column } else { line.chars().count() };
+        let width = start.line.to_string().len();
+        let pad = " ".repeat(width);
+        format!(
+            "{pad}--> {}:{}:{}\n{pad} |\n{} | {}\n{pad} | {}{}\n",
+            path.display(),
+            start.line,
+            start.column + 1,
+            start.line,
+            line,
+            " ".repeat(start.column),
+            "^".repeat(underline_end.saturating_sub(start.column).max(1)),
+        )
+    }
+
+    impl std::fmt::Display for Error {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Error::UnsupportedType(ty, _) => write!(f, "unsupported type `{}`", type_text(ty)),
+                Error::UnsupportedReturnType(ty, _) => write!(f, "unsupported return type `{}`", type_text(ty)),
+                Error::NoRecvSlot(method, _) => write!(f, "`{}` returns a capability, but has no `&AbsoluteCPtr` parameter to receive it in", method),
+                Error::MisplacedAttribute(attr, _) => write!(f, "`#[{}]` only applies to `&AbsoluteCPtr` parameters", attr),
+                Error::ConflictingDirections(param, _) => write!(f, "parameter `{}` can't be both `#[send]` and `#[recv]`", param),
+                Error::MultipleRecvSlots(method, _) => write!(f, "`{}` has more than one receive slot", method),
+                Error::UnknownHandleType(ty, _) => write!(f, "`{}` is not one of the configured handle types", type_text(ty)),
+                Error::TooManyCaps(method, count, _) => write!(f, "`{}` sends {} capabilities, at most {} fit in one message", method, count, crate::MSG_MAX_EXTRA_CAPS),
+                Error::NonIdentPattern(param, _) => write!(f, "parameter `{}` must be a plain identifier", type_text(param)),
+                Error::ReservedName(param, _) => write!(f, "parameter `{}` starts with `{}`, which is reserved for generated code", param, crate::input_node::input_node::RESERVED_PREFIX),
+                Error::TooManyRegisters(method, count, _) => write!(f, "`{}` needs {} message registers, at most {} are available", method, count, crate::MSG_MAX_LENGTH),
+                Error::TooManyOptionals(method, _) => write!(f, "`{}` has more than 64 optional parameters", method),
+                Error::InvalidLabel(attr, _) => write!(f, "invalid label attribute `{}`, expected `#[label = N]` with N below {}", attr, crate::MAX_LABEL),
+                Error::NoNextLabel(method, _) => write!(f, "`{}` follows the largest label, give it a `#[label = N]`", method),
+                Error::DuplicateLabel(method, label, _) => write!(f, "label {} of `{}` is already in use", label, method),
+                Error::DuplicateVariant(method, variant, _) => write!(f, "label variant `{}` of `{}` is already in use", variant, method),
+                Error::UnsupportedTraitItem(item, _) => write!(f, "unsupported trait item `{}`, only methods are allowed", item),
+                Error::NoClientType(name, _) => write!(f, "`{}` has no supertrait to implement the client for, add one or #[idl(client = \"...\")]", name),
+                Error::UnsupportedItem(item, _) => write!(f, "unsupported item `{}`, expected a trait, type alias, const, struct, enum or use", item),
+                Error::Parse(e) => write!(f, "{}", e),
+                Error::InFile(path, _, e) => write!(f, "{}: {}", path.display(), e),
+                Error::Multiple(errors) => {
+                    let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                    write!(f, "{}", messages.join("\n"))
+                },
+                Error::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+                Error::NoFileName(path) => write!(f, "{} does not name a file", path.display()),
+                Error::DuplicateModule(module, path) => write!(f, "{}: module `{}` is already generated for another input, rename the file", path.display(), module),
+                Error::NoOutDir => write!(f, "no output directory set and OUT_DIR is not defined"),
+            }
+        }
+    }
+}
@@ -0,0 +1,1522 @@
+pub mod input_node {
+    use proc_macro2::TokenStream;
+    use quote::{format_ident, quote, ToTokens};
+    use syn::{spanned::Spanned, parse_quote, parse_str, FnArg, GenericArgument, Ident, PatType, PathArguments, Type};
+    use crate::errors::errors::Error;
+    use crate::type_context::type_context::{IdlEnum, TypeContext};
+
+    pub trait InputType {
+        fn marshal_code(&self, ident: Ident, ty: Type, buffer_name: Ident, msg_index: &mut usize) -> TokenStream {
+            let idx = *msg_index;
+            let ret = self.into_ipc_buf(ident, ty, buffer_name, idx);
+            *msg_index += self.register_count();
+            ret 
+        }
+        fn unmarshal_code(&self, ty: Type, buffer_name: Ident, msg_index: &mut usize) -> TokenStream {
+            let idx = *msg_index;
+            let ret = self.from_ipc_buf(ty, buffer_name, idx);
+            *msg_index += self.register_count();
+            ret
+        }
+        #[allow(clippy::wrong_self_convention)]
+        fn into_ipc_buf(&self, ident: Ident, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream;
+        // expression reading the value back out of the buffer
+        #[allow(clippy::wrong_self_convention)]
+        fn from_ipc_buf(&self, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream;
+        // what the default is_type compares against, the types matched some
+        // other way have none
+        fn type_parses(&self) -> Vec<Type> {
+            vec![]
+        }
+        // whether the server sees this parameter at all
+        fn carries_data(&self, _ty: Type) -> bool {
+            true
+        }
+        fn is_type(&self, ty: syn::Type) -> bool {
+            self.type_parses().iter().any(|t| t.clone() == ty)
+        }
+        fn consumes_register(&self) -> bool;
+        fn register_count(&self) -> usize {
+            self.consumes_register() as usize
+        }
+        fn get_enum(&self) -> InputTypes;
+        // written back into the caller's arguments once the reply is in
+        fn copy_out(&self, _ident: Ident, _ty: Type) -> TokenStream {
+            quote! {}
+        }
+        // Some if the value fits one register word, so it can also go to a
+        // packed register or spill to the shared buffer
+        fn as_word(&self) -> Option<&dyn WordType> {
+            None
+        }
+        // the parameter's type in the server's handler
+        fn handler_type(&self, ty: Type) -> Type {
+            ty
+        }
+        // Some for values taking register_count() words that can spill as a whole
+        fn as_words(&self) -> Option<&dyn WordsType> {
+            None
+        }
+    }
+
+    // a value that fits one u64 register word
+    pub trait WordType {
+        #[allow(clippy::wrong_self_convention)]
+        fn to_word(&self, ident: Ident, ty: Type) -> TokenStream;
+        // the value back out of a register word
+        #[allow(clippy::wrong_self_convention)]
+        fn from_word(&self, ty: Type, word: TokenStream) -> TokenStream;
+        // bits needed when packed with others into one register, None if it can't be
+        fn bit_width(&self, _ty: &Type) -> Option<u32> {
+            None
+        }
+        // whether a packed value has to be sign extended before from_word
+        fn sign_extends(&self, _ty: &Type) -> bool {
+            false
+        }
+    }
+
+    // a value spanning several words, which go wherever Words says
+    pub trait WordsType {
+        #[allow(clippy::wrong_self_convention)]
+        fn to_words(&self, ident: Ident, ty: Type, words: &Words) -> TokenStream;
+        #[allow(clippy::wrong_self_convention)]
+        fn from_words(&self, ty: Type, words: &Words) -> TokenStream;
+    }
+
+    // where the words of a value spanning several of them go: the message
+    // registers from an index on, or the spill area from a word on
+    pub enum Words {
+        Registers(Ident, usize),
+        Spill(usize),
+    }
+
+    impl Words {
+        // word is the k-th of the value, i an extra index computed at runtime
+        fn put(&self, k: usize, i: Option<TokenStream>, word: TokenStream) -> TokenStream {
+            let index = self.index(k, i);
+            match self {
+                Words::Registers(buffer_name, _) => quote! {
+                    #buffer_name.msg_regs_mut()[#index] = #word;
+                },
+                Words::Spill(_) => quote! {
+                    __idl_shared_buf[__idl_spill_offset + 8 * (#index)..][..8].copy_from_slice(&(#word).to_ne_bytes());
+                },
+            }
+        }
+
+        fn get(&self, k: usize, i: Option<TokenStream>) -> TokenStream {
+            let index = self.index(k, i);
+            match self {
+                Words::Registers(buffer_name, _) => quote! { #buffer_name.msg_regs()[#index] },
+                Words::Spill(_) => quote! { __idl_spill_word(#index) },
+            }
+        }
+
+        fn index(&self, k: usize, i: Option<TokenStream>) -> TokenStream {
+            let first = match self {
+                Words::Registers(_, msg_index) => *msg_index,
+                Words::Spill(word_index) => *word_index,
+            };
+            let first = first + k;
+            match i {
+                Some(i) => quote! { #first + #i },
+                None => quote! { #first },
+            }
+        }
+    }
+
+    // generated locals start with this, so they can't shadow a parameter
+    pub const RESERVED_PREFIX: &str = "__idl_";
+
+    pub fn generated_ident(name: impl std::fmt::Display) -> Ident {
+        format_ident!("{}{}", RESERVED_PREFIX, name.to_string())
+    }
+
+    fn all_input_types() -> Vec<Box<dyn InputType>> {
+        vec![
+            Box::from(NumberType {}),
+            Box::from(BoolType {}),
+            Box::from(AbsoluteCPtrType {}),
+            Box::from(LocalHandleType {}),
+            Box::from(BufferType {}),
+            Box::from(OutType {}),
+            Box::from(SendCapType { cap_index: 0 }),
+        ]
+    }
+    
+    pub fn get_input_param(param: FnArg, context: &TypeContext) -> Result<InputParam, Error> {
+        let (var_name, ty) = if let FnArg::Typed(
+            PatType { ref pat, ref ty, .. }
+        ) = param {
+            let var_name = if let syn::Pat::Ident(pat_ident) = pat.as_ref() {
+                pat_ident.ident.to_string()
+            } else {
+                return Err(Error::NonIdentPattern(pat.to_token_stream().to_string(), pat.span()));
+            };
+            let ty_inner = ty.as_ref().clone();
+            (var_name, ty_inner)
+        } else {
+            return Err(Error::UnsupportedType(param.to_token_stream().to_string(), param.span()));
+        };
+        // matched resolved, emitted as written
+        let written = ty;
+        let ty = context.resolve(&written);
+        let mut input_type = match_type_in(ty.clone(), context)?;
+        if let FnArg::Typed(PatType { ref attrs, .. }) = param {
+            let send = attrs.iter().find(|a| a.path().is_ident("send"));
+            let recv = attrs.iter().find(|a| a.path().is_ident("recv"));
+            if let (Some(_), Some(recv)) = (send, recv) {
+                return Err(Error::ConflictingDirections(var_name, recv.span()));
+            }
+            match (send, recv) {
+                (Some(send), _) if !matches!(input_type, InputTypes::AbsoluteCptrType) => {
+                    return Err(Error::MisplacedAttribute(String::from("send"), send.span()));
+                },
+                (_, Some(recv)) if !is_recv_slot(&input_type, &ty) => {
+                    return Err(Error::MisplacedAttribute(String::from("recv"), recv.span()));
+                },
+                // indexed by the method, see assign_caps
+                (Some(_), _) => input_type = InputTypes::SendCapType(0),
+                _ => {}
+            }
+        }
+        Ok(
+            InputParam { ident: var_name, ty, written, input_type, presence: None, packed: None, spilled: None }
+        )
+    }
+
+
+    pub fn get_input_type(param: FnArg) -> Result<InputTypes, Error> {
+        let ty = if let FnArg::Typed(
+            PatType { ref ty, .. }
+        ) = param {
+            ty.as_ref().clone()
+        } else {
+            return Err(Error::UnsupportedType(param.to_token_stream().to_string(), param.span()));
+        };
+        match_type(ty)
+    }
+
+    // the types declared in the IDL are only known through the context, on
+    // their own, inside an optional or as an element. ty is already resolved
+    pub fn match_type_in(ty: Type, context: &TypeContext) -> Result<InputTypes, Error> {
+        if let Some(idl_enum) = context.get_enum(&ty) {
+            return EnumType::input_type(idl_enum);
+        }
+        if let Some(idl_flags) = context.get_flags(&ty) {
+            return Ok(InputTypes::FlagsType(idl_flags.bit_width()));
+        }
+        if OptionType::is_option(&ty) {
+            return OptionType::input_type(&ty, context);
+        }
+        match ty {
+            Type::Array(ref array) => return ArrayType::input_type(array, context),
+            Type::Tuple(ref tuple) if !tuple.elems.is_empty() => return TupleType::input_type(tuple, context),
+            _ => {}
+        }
+        for input_ty in all_input_types() {
+            if input_ty.is_type(ty.clone()) {
+                return Ok(input_ty.get_enum());
+            }
+        }
+        Err(Error::UnsupportedType(ty.to_token_stream().to_string(), ty.span()))
+    }
+
+    // without the types declared in the IDL
+    pub fn match_type(ty: Type) -> Result<InputTypes, Error> {
+        match_type_in(ty, &TypeContext::default())
+    }
+    #[allow(clippy::enum_variant_names)]
+    #[derive(Clone)]
+    pub enum InputTypes {
+        NumberType,
+        BoolType, 
+        LocalHandleType,
+        // with the type of the value when it is there
+        OptionType(Box<InputTypes>),
+        AbsoluteCptrType,
+        BufferType,
+        OutType,
+        // with its index in caps_or_badges
+        SendCapType(usize),
+        // only ever chosen through the TypeContext
+        EnumType(IdlEnum),
+        // with the width of the flags' bits
+        FlagsType(Option<u32>),
+        // with the length, the type of an element and its width
+        ArrayType(usize, Box<InputTypes>, Option<u32>),
+        // with the type and width of each element
+        TupleType(Vec<InputTypes>, Vec<Option<u32>>)
+    }
+
+    impl InputTypes {
+        pub fn get_input_type_impl(&self) -> Box<dyn InputType> {
+            match self {
+                InputTypes::AbsoluteCptrType => Box::from(AbsoluteCPtrType {}),
+                InputTypes::BoolType => Box::from(BoolType {}),
+                InputTypes::LocalHandleType => Box::from(LocalHandleType {}),
+                InputTypes::NumberType => Box::from(NumberType {}),
+                InputTypes::OptionType(inner) => Box::from(OptionType { inner: inner.clone() }),
+                InputTypes::BufferType => Box::from(BufferType {}),
+                InputTypes::OutType => Box::from(OutType {}),
+                InputTypes::SendCapType(cap_index) => Box::from(SendCapType { cap_index: *cap_index }),
+                InputTypes::EnumType(idl_enum) => Box::from(EnumType { idl_enum: idl_enum.clone() }),
+                InputTypes::FlagsType(bit_width) => Box::from(FlagsType { bit_width: *bit_width }),
+                InputTypes::ArrayType(len, elem, width) => Box::from(ArrayType { len: *len, elem: elem.clone(), width: *width }),
+                InputTypes::TupleType(elems, widths) => Box::from(TupleType { elems: elems.clone(), widths: widths.clone() })
+            }
+        }
+    }
+
+    // `&AbsoluteCPtr` and `Option<&AbsoluteCPtr>` name where a cap is received
+    fn is_recv_slot(input_type: &InputTypes, ty: &Type) -> bool {
+        match input_type {
+            InputTypes::AbsoluteCptrType => true,
+            InputTypes::OptionType(inner) => !OptionType { inner: inner.clone() }.carries_data(ty.clone()),
+            _ => false
+        }
+    }
+
+    pub struct InputParam {
+        pub ident: String,
+        // resolved, see TypeContext::resolve
+        ty: Type,
+        // as in the IDL, for the generated code to name
+        written: Type,
+        pub input_type: InputTypes,
+        // (register, bit) of the presence flag of an optional parameter
+        pub presence: Option<(usize, usize)>,
+        // (register, shift) of a parameter sharing a register with others
+        pub packed: Option<(usize, u32)>,
+        // word index in the spill area of the shared buffer, once registers run out
+        pub spilled: Option<usize>,
+    }
+
+    impl InputParam {
+        pub fn get_marshal_code(&self, ipc_buffer_name: String, msg_index: &mut usize) -> TokenStream {
+            let ident = format_ident!("{}", self.ident.clone());
+            let buffer_name = format_ident!("{}", ipc_buffer_name);
+            let input_type = self.input_type.get_input_type_impl();
+            if let (Some((register, shift)), Some(word_type)) = (self.packed, input_type.as_word()) {
+                let mask = self.packed_mask();
+                let word = word_type.to_word(ident, self.ty.clone());
+                return quote! {
+                    #buffer_name.msg_regs_mut()[#register] |= ((#word) & #mask) << #shift;
+                };
+            }
+            let marshal = match self.spilled {
+                Some(word_index) => {
+                    let (input_type, ty) = self.word_type();
+                    let start = 8 * word_index;
+                    let value_ident = if self.presence.is_some() { format_ident!("{}_inner", ident) } else { ident.clone() };
+                    let spill = match (input_type.as_words(), input_type.as_word()) {
+                        (Some(words_type), _) => words_type.to_words(value_ident.clone(), ty, &Words::Spill(word_index)),
+                        (None, Some(word_type)) => {
+                            let word = word_type.to_word(value_ident.clone(), ty);
+                            quote! {
+                                __idl_shared_buf[__idl_spill_offset + #start..][..8].copy_from_slice(&(#word).to_ne_bytes());
+                            }
+                        },
+                        (None, None) => unreachable!("only what spills() is spilled")
+                    };
+                    if self.presence.is_some() {
+                        quote! {
+                            if let Some(#value_ident) = #ident {
+                                #spill
+                            }
+                        }
+                    } else {
+                        spill
+                    }
+                },
+                None => self.input_type.get_input_type_impl().marshal_code(
+                    ident.clone(),
+                    self.ty.clone(), 
+                    buffer_name.clone(),
+                    msg_index
+                )
+            };
+            match self.presence {
+                Some((register, bit)) => quote! {
+                    #buffer_name.msg_regs_mut()[#register] |= (#ident.is_some() as u64) << #bit;
+                    #marshal
+                },
+                None => marshal
+            }
+        }
+
+        pub fn get_unmarshal_code(&self, ipc_buffer_name: String, msg_index: &mut usize) -> TokenStream {
+            let ident = format_ident!("{}", self.ident.clone());
+            let buffer_name = format_ident!("{}", ipc_buffer_name);
+            let ty = self.ty.clone();
+            let input_type = self.input_type.get_input_type_impl();
+            let written = input_type.handler_type(self.written.clone());
+            if let (Some((register, shift)), Some(word_type)) = (self.packed, input_type.as_word()) {
+                let mask = self.packed_mask();
+                let mut word = quote! { (#buffer_name.msg_regs()[#register] >> #shift) & #mask };
+                if word_type.sign_extends(&ty) {
+                    let spare = 64 - self.bit_width().unwrap_or(64);
+                    word = quote! { ((((#word) << #spare) as i64) >> #spare) as u64 };
+                }
+                let decode = word_type.from_word(ty.clone(), word);
+                return quote! {
+                    let #ident: #written = #decode;
+                };
+            }
+            let present = self.presence.map(|(register, bit)| quote! {
+                #buffer_name.msg_regs()[#register] & (1u64 << #bit) != 0
+            });
+            if !input_type.carries_data(ty.clone()) {
+                return match present {
+                    Some(present) => quote! { let #ident: bool = #present; },
+                    None => quote! {}
+                };
+            }
+            let decode = match self.spilled {
+                Some(word_index) => {
+                    let (input_type, word_ty) = self.word_type();
+                    let word = match (input_type.as_words(), input_type.as_word()) {
+                        (Some(words_type), _) => words_type.from_words(word_ty, &Words::Spill(word_index)),
+                        (None, Some(word_type)) => word_type.from_word(word_ty, quote! { __idl_spill_word(#word_index) }),
+                        (None, None) => unreachable!("only what spills() is spilled")
+                    };
+                    if self.presence.is_some() {
+                        quote! { Some(#word) }
+                    } else {
+                        word
+                    }
+                },
+                None => input_type.unmarshal_code(ty.clone(), buffer_name, msg_index)
+            };
+            match present {
+                Some(present) => quote! {
+                    let #ident: #written = if #present { #decode } else { None };
+                },
+                None => quote! {
+                    let #ident: #written = #decode;
+                }
+            }
+        }
+
+        pub fn get_copy_out_code(&self) -> TokenStream {
+            self.input_type.get_input_type_impl().copy_out(format_ident!("{}", self.ident), self.ty.clone())
+        }
+
+        pub fn is_recv_slot(&self) -> bool {
+            is_recv_slot(&self.input_type, &self.ty)
+        }
+
+        // a buffer the server writes into
+        pub fn is_mut_buffer(&self) -> bool {
+            matches!(self.input_type, InputTypes::BufferType) && BufferType::is_mut(&self.ty)
+        }
+
+        // what is written to the register, the inner value for optionals
+        fn word_type(&self) -> (Box<dyn InputType>, Type) {
+            match self.input_type {
+                InputTypes::OptionType(ref inner) => {
+                    (inner.get_input_type_impl(), OptionType::get_inner_type(self.ty.clone()).unwrap())
+                },
+                _ => (self.input_type.get_input_type_impl(), self.ty.clone())
+            }
+        }
+
+        pub fn register_count(&self) -> usize {
+            if self.packed.is_some() {
+                0
+            } else {
+                self.word_type().0.register_count()
+            }
+        }
+
+        pub fn fits_word(&self) -> bool {
+            self.word_type().0.as_word().is_some()
+        }
+
+        // whether the value can go to the spill area instead of registers
+        pub fn spills(&self) -> bool {
+            self.fits_word() || self.word_type().0.as_words().is_some()
+        }
+
+        pub fn bit_width(&self) -> Option<u32> {
+            self.input_type.get_input_type_impl().as_word().and_then(|word_type| word_type.bit_width(&self.ty))
+        }
+
+        fn packed_mask(&self) -> u64 {
+            self.bit_width().map_or(u64::MAX, |width| (1u64 << width) - 1)
+        }
+
+        pub fn ty(&self) -> &Type {
+            &self.ty
+        }
+
+        // the parameter's type as the server sees it, None if it doesn't see it at all
+        pub fn handler_type(&self) -> Option<Type> {
+            let input_type = self.input_type.get_input_type_impl();
+            if input_type.carries_data(self.ty.clone()) {
+                Some(input_type.handler_type(self.written.clone()))
+            } else if self.presence.is_some() {
+                // an optional cap only tells the server whether it was sent
+                Some(parse_quote! { bool })
+            } else {
+                None
+            }
+        }
+    }
+
+    const UNSIGNED: [&str; 5] = ["u8", "u16", "u32", "u64", "usize"];
+    const SIGNED: [&str; 5] = ["i8", "i16", "i32", "i64", "isize"];
+
+    // NonZeroU32 -> u32
+    fn non_zero_inner(ty: &Type) -> Option<Ident> {
+        let name = ty.to_token_stream().to_string();
+        let inner = name.strip_prefix("NonZero")?.to_lowercase();
+        if UNSIGNED.contains(&inner.as_str()) || SIGNED.contains(&inner.as_str()) {
+            Some(format_ident!("{}", inner))
+        } else {
+            None
+        }
+    }
+
+    struct NumberType {}
+    impl InputType for NumberType {
+        fn into_ipc_buf(&self, ident: Ident, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            let word = self.to_word(ident, ty);
+            quote! {
+                #buffer_name.msg_regs_mut()[#msg_index] = #word;
+            }
+        }
+
+        fn from_ipc_buf(&self, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            self.from_word(ty, quote! { #buffer_name.msg_regs()[#msg_index] })
+        }
+
+        fn as_word(&self) -> Option<&dyn WordType> {
+            Some(self)
+        }
+
+        fn type_parses(&self) -> Vec<syn::Type> {
+            let mut types: Vec<Type> = UNSIGNED
+                .iter()
+                .chain(SIGNED.iter())
+                .flat_map(|name| {
+                    let non_zero = format!("NonZero{}{}", name[..1].to_uppercase(), &name[1..]);
+                    [parse_str(name).expect("Couldn't parse"), parse_str(&non_zero).expect("Couldn't parse")]
+                })
+                .collect();
+            types.push(parse_str("char").expect("Couldn't parse"));
+            types.push(parse_str("*const u8").expect("Couldn't parse"));
+            types.push(parse_str("*mut u8").expect("Couldn't parse"));
+            types
+        }
+        fn consumes_register(&self) -> bool {
+            true
+        }
+        fn get_enum(&self) -> InputTypes {
+            InputTypes::NumberType
+        }
+    }
+
+    impl WordType for NumberType {
+        // signed values are sign extended to the full register
+        fn to_word(&self, ident: Ident, ty: Type) -> TokenStream {
+            if non_zero_inner(&ty).is_some() {
+                quote! { #ident.get() as u64 }
+            } else {
+                quote! { #ident as u64 }
+            }
+        }
+
+        // values that don't fit the type are rejected rather than truncated
+        fn from_word(&self, ty: Type, word: TokenStream) -> TokenStream {
+            let name = ty.to_token_stream().to_string();
+            if let Type::Ptr(_) = ty {
+                return quote! { #word as #ty };
+            }
+            if let Some(inner) = non_zero_inner(&ty) {
+                let inner_decode = self.from_word(parse_quote! { #inner }, word);
+                return quote! {
+                    core::num::#ty::new(#inner_decode).ok_or(smos_common::error::InvocationError::InvalidArguments)?
+                };
+            }
+            match name.as_str() {
+                "u64" => word,
+                "i64" => quote! { #word as i64 },
+                "char" => quote! {
+                    u32::try_from(#word)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or(smos_common::error::InvocationError::InvalidArguments)?
+                },
+                _ if SIGNED.contains(&name.as_str()) => quote! {
+                    #ty::try_from(#word as i64).map_err(|_| smos_common::error::InvocationError::InvalidArguments)?
+                },
+                _ => quote! {
+                    #ty::try_from(#word).map_err(|_| smos_common::error::InvocationError::InvalidArguments)?
+                },
+            }
+        }
+
+        fn bit_width(&self, ty: &Type) -> Option<u32> {
+            let name = match non_zero_inner(ty) {
+                Some(inner) => inner.to_string(),
+                None => ty.to_token_stream().to_string(),
+            };
+            match name.as_str() {
+                "u8" | "i8" => Some(8),
+                "u16" | "i16" => Some(16),
+                // the largest scalar value is 0x10FFFF
+                "char" => Some(21),
+                "u32" | "i32" => Some(32),
+                _ => None,
+            }
+        }
+
+        fn sign_extends(&self, ty: &Type) -> bool {
+            let name = match non_zero_inner(ty) {
+                Some(inner) => inner.to_string(),
+                None => ty.to_token_stream().to_string(),
+            };
+            SIGNED.contains(&name.as_str())
+        }
+    }
+
+    struct BoolType {}
+    impl InputType for BoolType {
+        fn into_ipc_buf(&self, ident: Ident, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            let word = self.to_word(ident, ty);
+            quote! {
+                #buffer_name.msg_regs_mut()[#msg_index] = #word;
+            }
+        }
+
+        fn from_ipc_buf(&self, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            self.from_word(ty, quote! { #buffer_name.msg_regs()[#msg_index] })
+        }
+
+        fn as_word(&self) -> Option<&dyn WordType> {
+            Some(self)
+        }
+
+        fn type_parses(&self) -> Vec<syn::Type> {
+            vec![
+                parse_str("bool").expect("Couldn't parse"),
+            ]
+        }
+        fn consumes_register(&self) -> bool {
+            true
+        }
+        fn get_enum(&self) -> InputTypes {
+            InputTypes::BoolType
+        }
+    }
+
+    impl WordType for BoolType {
+        fn to_word(&self, ident: Ident, _: Type) -> TokenStream {
+            quote! { #ident as u64 }
+        }
+
+        fn from_word(&self, _: Type, word: TokenStream) -> TokenStream {
+            quote! { #word != 0 }
+        }
+
+        fn bit_width(&self, _: &Type) -> Option<u32> {
+            Some(1)
+        }
+    }
+
+    struct AbsoluteCPtrType {}
+    impl InputType for AbsoluteCPtrType {
+        fn into_ipc_buf(&self, ident: Ident, _: Type, buffer_name: Ident, _msg_index: usize) -> TokenStream {
+            quote! {
+                #buffer_name.set_recv_slot(#ident);
+            }
+        }
+
+        fn from_ipc_buf(&self, _: Type, _: Ident, _: usize) -> TokenStream {
+            unimplemented!() // never called, receive slots carry no data
+        }
+
+        fn carries_data(&self, _: Type) -> bool {
+            false
+        }
+
+        fn type_parses(&self) -> Vec<syn::Type> {
+            vec![
+                parse_str("&AbsoluteCPtr").expect("Couldn't parse")
+            ]
+        }
+        fn consumes_register(&self) -> bool {
+            false
+        }
+        fn get_enum(&self) -> InputTypes {
+            InputTypes::AbsoluteCptrType
+        }
+    }
+
+    struct LocalHandleType {}
+    impl InputType for LocalHandleType {
+        fn into_ipc_buf(&self, ident: Ident, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            let word = self.to_word(ident, ty);
+            quote! {
+                #buffer_name.msg_regs_mut()[#msg_index] = #word;
+            }
+        }
+        fn from_ipc_buf(&self, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            self.from_word(ty, quote! { #buffer_name.msg_regs()[#msg_index] })
+        }
+        fn as_word(&self) -> Option<&dyn WordType> {
+            Some(self)
+        }
+
+        // any `&LocalHandle<T>`, T is checked against the TypeContext
+        fn is_type(&self, ty: Type) -> bool {
+            match ty {
+                Type::Reference(reference) if reference.mutability.is_none() => handle_inner(&reference.elem).is_some(),
+                _ => false
+            }
+        }
+        fn consumes_register(&self) -> bool {
+            true
+        }
+        fn get_enum(&self) -> InputTypes {
+            InputTypes::LocalHandleType
+        }
+    }
+
+    impl WordType for LocalHandleType {
+        fn to_word(&self, ident: Ident, _: Type) -> TokenStream {
+            quote! { #ident.idx as u64 }
+        }
+        // parameters borrow the handle, returns own it. T is left to inference,
+        // the IDL may name it by a path that isn't in scope once resolved
+        fn from_word(&self, ty: Type, word: TokenStream) -> TokenStream {
+            match ty {
+                Type::Reference(_) => quote! { &smos_common::local_handle::LocalHandle::new(#word as usize) },
+                _ => quote! { smos_common::local_handle::LocalHandle::new(#word as usize) }
+            }
+        }
+    }
+
+    pub struct OptionType {
+        inner: Box<InputTypes>,
+    }
+    impl InputType for OptionType {
+        // the code here is really shit
+        fn into_ipc_buf(&self, ident: Ident, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            let inner_type = Self::get_inner_type(ty.clone()).unwrap();
+            
+            let inner_ident = format_ident!("{}_inner", ident);
+            let inner_type_marshall = self.inner
+                .get_input_type_impl()
+                .into_ipc_buf(inner_ident.clone(), inner_type, buffer_name, msg_index);
+
+            let ret = quote! {
+                if let Some(#inner_ident) = #ident {
+                    #inner_type_marshall
+                }
+            };
+            ret
+        }
+
+        // only valid once the presence flag has been checked
+        fn from_ipc_buf(&self, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            let inner_type = Self::get_inner_type(ty).unwrap();
+            let inner_decode = self.inner
+                .get_input_type_impl()
+                .from_ipc_buf(inner_type, buffer_name, msg_index);
+            quote! {
+                Some(#inner_decode)
+            }
+        }
+
+        fn carries_data(&self, ty: Type) -> bool {
+            let inner_type = Self::get_inner_type(ty).unwrap();
+            self.inner
+                .get_input_type_impl()
+                .carries_data(inner_type)
+        }
+
+        // only ever chosen by match_type_in, which knows the inner type
+        fn is_type(&self, _: Type) -> bool {
+            false
+        }
+        // laid out like the value, the presence bit is planned by the method
+        fn consumes_register(&self) -> bool {
+            self.inner.get_input_type_impl().consumes_register()
+        }
+        fn register_count(&self) -> usize {
+            self.inner.get_input_type_impl().register_count()
+        }
+        fn get_enum(&self) -> InputTypes {
+            InputTypes::OptionType(self.inner.clone())
+        }
+    }
+
+    impl OptionType {
+        fn is_option(ty: &Type) -> bool {
+            match ty {
+                Type::Path(type_path) => type_path.path.segments.last().is_some_and(|seg| seg.ident == "Option"),
+                _ => false
+            }
+        }
+
+        // buffers have no room to be left out of the layout, the presence
+        // bit can only say whether one level is there, and nothing would
+        // copy an output back out of the Some
+        fn input_type(ty: &Type, context: &TypeContext) -> Result<InputTypes, Error> {
+            let inner = match_type_in(Self::get_inner_type(ty.clone())?, context)?;
+            if matches!(inner, InputTypes::BufferType | InputTypes::OptionType(_) | InputTypes::OutType) {
+                return Err(Error::UnsupportedType(ty.to_token_stream().to_string(), ty.span()));
+            }
+            Ok(InputTypes::OptionType(Box::new(inner)))
+        }
+
+        fn get_inner_type(ty: Type) -> Result<Type, Error> {
+            if let Type::Path(ref type_path) = ty {
+                if let Some(seg) = type_path.path.segments.last() {
+                    if let PathArguments::AngleBracketed(ref bracketed_args) = seg.arguments {
+                        if bracketed_args.args.len() != 1 {
+                            return Err(Error::UnsupportedType(ty.to_token_stream().to_string(), ty.span()));
+                        }
+                        let gen_arg = bracketed_args.args.first().unwrap();
+                        match gen_arg {
+                            GenericArgument::Type(inner_ty) => {
+                                Ok(inner_ty.clone())
+                            },
+                            _ => Err(Error::UnsupportedType(ty.to_token_stream().to_string(), ty.span()))
+                        }
+                    } else {
+                        Err(Error::UnsupportedType(ty.to_token_stream().to_string(), ty.span()))
+                    }
+                } else {
+                    Err(Error::UnsupportedType(ty.to_token_stream().to_string(), ty.span()))
+                }
+            } else {
+                Err(Error::UnsupportedType(ty.to_token_stream().to_string(), ty.span()))
+            }
+        }
+    }
+
+    // variable-length data copied through the shared buffer, with its offset
+    // and length in two registers. the offsets are planned by the method
+    struct BufferType {}
+    impl BufferType {
+        fn is_str(ty: &Type) -> bool {
+            let name = ty.to_token_stream().to_string();
+            name == "& str" || name == "String"
+        }
+
+        fn is_mut(ty: &Type) -> bool {
+            ty.to_token_stream().to_string() == "& mut [u8]"
+        }
+    }
+    impl InputType for BufferType {
+        fn into_ipc_buf(&self, ident: Ident, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            let offset = generated_ident(format!("{}_offset", ident));
+            let bytes = if Self::is_str(&ty) {
+                quote! { #ident.as_bytes() }
+            } else {
+                quote! { &#ident[..] }
+            };
+            let len_index = msg_index + 1;
+            // a mutable buffer is only copied out, see copy_out
+            let copy_in = if Self::is_mut(&ty) {
+                quote! {}
+            } else {
+                quote! { __idl_shared_buf[#offset..][..#ident.len()].copy_from_slice(#bytes); }
+            };
+            quote! {
+                #copy_in
+                #buffer_name.msg_regs_mut()[#msg_index] = #offset as u64;
+                #buffer_name.msg_regs_mut()[#len_index] = #ident.len() as u64;
+            }
+        }
+
+        fn from_ipc_buf(&self, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            let len_index = msg_index + 1;
+            let name = ty.to_token_stream().to_string();
+            // every slice comes straight off the raw pointer, and overlap with a
+            // mutable buffer is ruled out before decoding
+            if Self::is_mut(&ty) {
+                return quote! {
+                    {
+                        let offset = #buffer_name.msg_regs()[#msg_index] as usize;
+                        let len = #buffer_name.msg_regs()[#len_index] as usize;
+                        if offset.checked_add(len).filter(|end| *end <= __idl_shared_buf_raw.1).is_none() {
+                            return Err(smos_common::error::InvocationError::InvalidArguments);
+                        }
+                        unsafe { core::slice::from_raw_parts_mut(__idl_shared_buf_raw.0.add(offset), len) }
+                    }
+                };
+            }
+            let value = match name.as_str() {
+                "& str" => quote! {
+                    core::str::from_utf8(bytes).map_err(|_| smos_common::error::InvocationError::InvalidArguments)?
+                },
+                "String" => quote! {
+                    String::from(core::str::from_utf8(bytes).map_err(|_| smos_common::error::InvocationError::InvalidArguments)?)
+                },
+                "Vec < u8 >" => quote! { bytes.to_vec() },
+                _ => quote! { bytes },
+            };
+            quote! {
+                {
+                    let offset = #buffer_name.msg_regs()[#msg_index] as usize;
+                    let len = #buffer_name.msg_regs()[#len_index] as usize;
+                    if offset.checked_add(len).filter(|end| *end <= __idl_shared_buf_raw.1).is_none() {
+                        return Err(smos_common::error::InvocationError::InvalidArguments);
+                    }
+                    let bytes: &[u8] = unsafe { core::slice::from_raw_parts(__idl_shared_buf_raw.0.add(offset), len) };
+                    #value
+                }
+            }
+        }
+
+        fn type_parses(&self) -> Vec<syn::Type> {
+            vec![
+                parse_str("&str").expect("Couldn't parse"),
+                parse_str("&[u8]").expect("Couldn't parse"),
+                parse_str("&mut [u8]").expect("Couldn't parse"),
+                parse_str("String").expect("Couldn't parse"),
+                parse_str("Vec<u8>").expect("Couldn't parse"),
+            ]
+        }
+        fn consumes_register(&self) -> bool {
+            true
+        }
+        fn register_count(&self) -> usize {
+            2
+        }
+        fn copy_out(&self, ident: Ident, ty: Type) -> TokenStream {
+            if !Self::is_mut(&ty) {
+                return quote! {};
+            }
+            let offset = generated_ident(format!("{}_offset", ident));
+            quote! {
+                #ident.copy_from_slice(&__idl_shared_buf[#offset..][..#ident.len()]);
+            }
+        }
+        fn get_enum(&self) -> InputTypes {
+            InputTypes::BufferType
+        }
+    }
+
+    // `#[send] &AbsoluteCPtr`, a cap transferred to the server. the server sees
+    // the badge if the kernel unwrapped it, None if it landed in its receive
+    // slot. the cap's index in the message is planned by the method
+    struct SendCapType {
+        cap_index: usize,
+    }
+    impl InputType for SendCapType {
+        fn into_ipc_buf(&self, ident: Ident, _: Type, buffer_name: Ident, _: usize) -> TokenStream {
+            let cap_index = self.cap_index;
+            quote! {
+                #buffer_name.caps_or_badges_mut()[#cap_index] = #ident.path().bits();
+            }
+        }
+
+        // __idl_caps_unwrapped is read off the MessageInfo by the dispatcher
+        fn from_ipc_buf(&self, _: Type, buffer_name: Ident, _: usize) -> TokenStream {
+            let cap_index = self.cap_index;
+            quote! {
+                if __idl_caps_unwrapped & (1 << #cap_index) != 0 {
+                    Some(#buffer_name.caps_or_badges()[#cap_index])
+                } else {
+                    None
+                }
+            }
+        }
+
+        fn handler_type(&self, _: Type) -> Type {
+            parse_quote! { Option<u64> }
+        }
+
+        // only ever chosen by the `#[send]` attribute
+        fn is_type(&self, _: Type) -> bool {
+            false
+        }
+        fn consumes_register(&self) -> bool {
+            false
+        }
+        fn get_enum(&self) -> InputTypes {
+            InputTypes::SendCapType(self.cap_index)
+        }
+    }
+
+    // an enum declared in the IDL file. a C-like one is its discriminant,
+    // checked by the generated TryFrom<u64>. a data-carrying one is the index
+    // of its variant followed by the variant's fields, with room for the
+    // largest variant
+    pub struct EnumType {
+        idl_enum: IdlEnum,
+    }
+
+    impl EnumType {
+        // every field of every variant has to fit a register of its own
+        pub fn input_type(idl_enum: IdlEnum) -> Result<InputTypes, Error> {
+            for variant in &idl_enum.variants {
+                for (_, ty) in &variant.fields {
+                    if !matches!(match_type(ty.clone()), Ok(InputTypes::NumberType | InputTypes::BoolType)) {
+                        return Err(Error::UnsupportedType(ty.to_token_stream().to_string(), ty.span()));
+                    }
+                }
+            }
+            Ok(InputTypes::EnumType(idl_enum))
+        }
+
+        fn payload_registers(&self) -> usize {
+            self.idl_enum.variants.iter().map(|variant| variant.fields.len()).max().unwrap_or(0)
+        }
+
+    }
+
+    impl InputType for EnumType {
+        fn into_ipc_buf(&self, ident: Ident, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            if self.idl_enum.is_c_like() {
+                let word = self.to_word(ident, ty);
+                return quote! {
+                    #buffer_name.msg_regs_mut()[#msg_index] = #word;
+                };
+            }
+            let enum_ident = &self.idl_enum.ident;
+            let arms = self.idl_enum.variants.iter().enumerate().map(|(index, variant)| {
+                let variant_ident = &variant.ident;
+                let index = index as u64;
+                let members = variant.fields.iter().map(|(member, _)| member);
+                let bindings: Vec<Ident> = (0..variant.fields.len()).map(|k| format_ident!("{}_{}", ident, k)).collect();
+                let writes = variant.fields.iter().zip(&bindings).enumerate().map(|(k, ((_, field_ty), binding))| {
+                    let register = msg_index + 1 + k;
+                    let word = scalar_word(field_ty).to_word(binding.clone(), field_ty.clone());
+                    quote! { #buffer_name.msg_regs_mut()[#register] = #word; }
+                });
+                quote! {
+                    #enum_ident::#variant_ident { #(#members: #bindings),* } => {
+                        #buffer_name.msg_regs_mut()[#msg_index] = #index;
+                        #(#writes)*
+                    }
+                }
+            });
+            quote! {
+                match #ident {
+                    #(#arms)*
+                }
+            }
+        }
+
+        fn from_ipc_buf(&self, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            if self.idl_enum.is_c_like() {
+                return self.from_word(ty, quote! { #buffer_name.msg_regs()[#msg_index] });
+            }
+            let enum_ident = &self.idl_enum.ident;
+            let arms = self.idl_enum.variants.iter().enumerate().map(|(index, variant)| {
+                let variant_ident = &variant.ident;
+                let index = index as u64;
+                let fields = variant.fields.iter().enumerate().map(|(k, (member, field_ty))| {
+                    let register = msg_index + 1 + k;
+                    let decode = scalar_word(field_ty).from_word(field_ty.clone(), quote! { #buffer_name.msg_regs()[#register] });
+                    quote! { #member: #decode }
+                });
+                quote! {
+                    #index => #enum_ident::#variant_ident { #(#fields),* },
+                }
+            });
+            quote! {
+                match #buffer_name.msg_regs()[#msg_index] {
+                    #(#arms)*
+                    _ => Err(smos_common::error::InvocationError::InvalidArguments)?,
+                }
+            }
+        }
+
+        // only a C-like enum is a single word
+        fn as_word(&self) -> Option<&dyn WordType> {
+            self.idl_enum.is_c_like().then_some(self as &dyn WordType)
+        }
+
+        fn is_type(&self, _: Type) -> bool {
+            false
+        }
+        fn consumes_register(&self) -> bool {
+            true
+        }
+        fn register_count(&self) -> usize {
+            1 + self.payload_registers()
+        }
+        fn get_enum(&self) -> InputTypes {
+            InputTypes::EnumType(self.idl_enum.clone())
+        }
+    }
+
+    impl WordType for EnumType {
+        fn to_word(&self, ident: Ident, _: Type) -> TokenStream {
+            quote! { #ident as u64 }
+        }
+
+        fn from_word(&self, ty: Type, word: TokenStream) -> TokenStream {
+            quote! { #ty::try_from(#word)? }
+        }
+
+        // enough bits for the largest discriminant, if they are all known
+        fn bit_width(&self, _: &Type) -> Option<u32> {
+            if !self.idl_enum.is_c_like() {
+                return None;
+            }
+            let mut largest = 0;
+            for variant in &self.idl_enum.variants {
+                match variant.discriminant {
+                    Some(discriminant) if discriminant >= 0 => largest = largest.max(discriminant),
+                    _ => return None
+                }
+            }
+            Some((128 - largest.leading_zeros()).max(1))
+        }
+    }
+
+    // an `#[idl(flags)]` type, its bits in one register. the generated
+    // TryFrom<u64> applies the type's unknown bit policy
+    pub struct FlagsType {
+        bit_width: Option<u32>,
+    }
+
+    impl InputType for FlagsType {
+        fn into_ipc_buf(&self, ident: Ident, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            let word = self.to_word(ident, ty);
+            quote! {
+                #buffer_name.msg_regs_mut()[#msg_index] = #word;
+            }
+        }
+
+        fn from_ipc_buf(&self, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            self.from_word(ty, quote! { #buffer_name.msg_regs()[#msg_index] })
+        }
+
+        fn as_word(&self) -> Option<&dyn WordType> {
+            Some(self)
+        }
+
+        fn is_type(&self, _: Type) -> bool {
+            false
+        }
+        fn consumes_register(&self) -> bool {
+            true
+        }
+        fn get_enum(&self) -> InputTypes {
+            InputTypes::FlagsType(self.bit_width)
+        }
+    }
+
+    impl WordType for FlagsType {
+        fn to_word(&self, ident: Ident, _: Type) -> TokenStream {
+            quote! { #ident.bits() as u64 }
+        }
+
+        fn from_word(&self, ty: Type, word: TokenStream) -> TokenStream {
+            quote! { #ty::try_from(#word)? }
+        }
+
+        fn bit_width(&self, _: &Type) -> Option<u32> {
+            self.bit_width
+        }
+    }
+
+    // an enum field, checked to be a number or a bool when the type was matched
+    fn scalar_word(ty: &Type) -> Box<dyn WordType> {
+        match match_type(ty.clone()) {
+            Ok(InputTypes::BoolType) => Box::from(BoolType {}),
+            _ => Box::from(NumberType {}),
+        }
+    }
+
+    // an array or tuple element, checked to fit a word by element_type
+    fn element_word(elem: &InputTypes) -> Box<dyn WordType> {
+        match elem {
+            InputTypes::BoolType => Box::from(BoolType {}),
+            InputTypes::EnumType(idl_enum) => Box::from(EnumType { idl_enum: idl_enum.clone() }),
+            InputTypes::FlagsType(bit_width) => Box::from(FlagsType { bit_width: *bit_width }),
+            _ => Box::from(NumberType {}),
+        }
+    }
+
+    // what an element of an array or tuple can be: a number, a bool, a C-like
+    // enum or flags, with its width if it packs
+    fn element_type(elem: &Type, context: &TypeContext) -> Result<(InputTypes, Option<u32>), Error> {
+        match match_type_in(elem.clone(), context) {
+            Ok(input_type @ (InputTypes::NumberType | InputTypes::BoolType | InputTypes::FlagsType(_))) => {
+                let width = element_word(&input_type).bit_width(elem);
+                Ok((input_type, width))
+            },
+            Ok(InputTypes::EnumType(idl_enum)) if idl_enum.is_c_like() => {
+                let input_type = InputTypes::EnumType(idl_enum);
+                let width = element_word(&input_type).bit_width(elem);
+                Ok((input_type, width))
+            },
+            _ => Err(Error::UnsupportedType(elem.to_token_stream().to_string(), elem.span()))
+        }
+    }
+
+    // (word, shift) of a packed element, taken back out of its word
+    fn unpack_element(input_type: &dyn WordType, elem: &Type, word: TokenStream, shift: TokenStream, width: u32) -> TokenStream {
+        let mask = (1u64 << width) - 1;
+        let mut word = quote! { (#word >> #shift) & #mask };
+        if input_type.sign_extends(elem) {
+            let spare = 64 - width;
+            word = quote! { ((((#word) << #spare) as i64) >> #spare) as u64 };
+        }
+        input_type.from_word(elem.clone(), word)
+    }
+
+    // `[T; N]` of numbers, bools or flags, with elements narrower than a
+    // register packed several to a word. N has to be an integer literal
+    pub struct ArrayType {
+        len: usize,
+        elem: Box<InputTypes>,
+        width: Option<u32>,
+    }
+
+    impl ArrayType {
+        pub fn input_type(array: &syn::TypeArray, context: &TypeContext) -> Result<InputTypes, Error> {
+            let len = match &array.len {
+                syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(len), .. }) => len.base10_parse().ok(),
+                _ => None
+            };
+            let len = len.ok_or(Error::UnsupportedType(array.len.to_token_stream().to_string(), array.len.span()))?;
+            // decoding starts from an array of defaults, which IDL enums needn't have
+            let elem = array.elem.as_ref();
+            let (elem_type, width) = element_type(elem, context)?;
+            if matches!(elem, Type::Ptr(_)) || non_zero_inner(elem).is_some() || matches!(elem_type, InputTypes::EnumType(_)) {
+                return Err(Error::UnsupportedType(elem.to_token_stream().to_string(), elem.span()));
+            }
+            Ok(InputTypes::ArrayType(len, Box::new(elem_type), width))
+        }
+
+        fn per_word(&self) -> usize {
+            self.width.map_or(1, |width| (64 / width) as usize)
+        }
+
+        fn elem(ty: &Type) -> Type {
+            match ty {
+                Type::Array(array) => array.elem.as_ref().clone(),
+                _ => unreachable!()
+            }
+        }
+    }
+
+    impl InputType for ArrayType {
+        fn into_ipc_buf(&self, ident: Ident, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            self.to_words(ident, ty, &Words::Registers(buffer_name, msg_index))
+        }
+
+        fn from_ipc_buf(&self, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            self.from_words(ty, &Words::Registers(buffer_name, msg_index))
+        }
+
+        fn as_words(&self) -> Option<&dyn WordsType> {
+            Some(self)
+        }
+
+        fn is_type(&self, _: Type) -> bool {
+            false
+        }
+        fn consumes_register(&self) -> bool {
+            self.len > 0
+        }
+        fn register_count(&self) -> usize {
+            self.len.div_ceil(self.per_word())
+        }
+        fn get_enum(&self) -> InputTypes {
+            InputTypes::ArrayType(self.len, self.elem.clone(), self.width)
+        }
+    }
+
+    impl WordsType for ArrayType {
+        fn to_words(&self, ident: Ident, ty: Type, words: &Words) -> TokenStream {
+            let elem_ty = Self::elem(&ty);
+            let word = element_word(&self.elem).to_word(format_ident!("elem"), elem_ty);
+            let (per_word, width) = match self.width {
+                Some(width) if self.per_word() > 1 => (self.per_word(), width),
+                _ => {
+                    let put = words.put(0, Some(quote! { i }), word);
+                    return quote! {
+                        for (i, &elem) in #ident.iter().enumerate() {
+                            #put
+                        }
+                    };
+                }
+            };
+            let mask = (1u64 << width) - 1;
+            let put = words.put(0, Some(quote! { i }), quote! { word });
+            quote! {
+                for (i, chunk) in #ident.chunks(#per_word).enumerate() {
+                    let mut word = 0u64;
+                    for (k, &elem) in chunk.iter().enumerate() {
+                        word |= ((#word) & #mask) << (k as u32 * #width);
+                    }
+                    #put
+                }
+            }
+        }
+
+        fn from_words(&self, ty: Type, words: &Words) -> TokenStream {
+            let elem_ty = Self::elem(&ty);
+            let len = self.len;
+            let decode = match self.width {
+                Some(width) if self.per_word() > 1 => {
+                    let per_word = self.per_word();
+                    let word = words.get(0, Some(quote! { i / #per_word }));
+                    unpack_element(element_word(&self.elem).as_ref(), &elem_ty, word, quote! { ((i % #per_word) as u32 * #width) }, width)
+                },
+                _ => {
+                    let word = words.get(0, Some(quote! { i }));
+                    element_word(&self.elem).from_word(elem_ty.clone(), word)
+                }
+            };
+            quote! {
+                {
+                    let mut elems = [<#elem_ty as Default>::default(); #len];
+                    for (i, elem) in elems.iter_mut().enumerate() {
+                        *elem = #decode;
+                    }
+                    elems
+                }
+            }
+        }
+    }
+
+    // `(A, B, ..)` of numbers, bools, C-like enums or flags, consecutive
+    // narrow elements sharing a word while they fit
+    pub struct TupleType {
+        elems: Vec<InputTypes>,
+        widths: Vec<Option<u32>>,
+    }
+
+    impl TupleType {
+        pub fn input_type(tuple: &syn::TypeTuple, context: &TypeContext) -> Result<InputTypes, Error> {
+            let (elems, widths) = tuple.elems
+                .iter()
+                .map(|elem| element_type(elem, context))
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .unzip();
+            Ok(InputTypes::TupleType(elems, widths))
+        }
+
+        // (word, shift) of every element, and the number of words
+        fn layout(&self) -> (Vec<(usize, u32)>, usize) {
+            let mut layout = vec![];
+            let mut words = 0;
+            // bits used of the last word, 64 if it can't be shared
+            let mut used = 64;
+            for width in &self.widths {
+                match width {
+                    Some(width) if used + width <= 64 => {
+                        layout.push((words - 1, used));
+                        used += width;
+                    },
+                    _ => {
+                        layout.push((words, 0));
+                        words += 1;
+                        used = width.unwrap_or(64);
+                    }
+                }
+            }
+            (layout, words)
+        }
+
+        fn elems(ty: &Type) -> Vec<Type> {
+            match ty {
+                Type::Tuple(tuple) => tuple.elems.iter().cloned().collect(),
+                _ => unreachable!()
+            }
+        }
+    }
+
+    impl InputType for TupleType {
+        fn into_ipc_buf(&self, ident: Ident, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            self.to_words(ident, ty, &Words::Registers(buffer_name, msg_index))
+        }
+
+        fn from_ipc_buf(&self, ty: Type, buffer_name: Ident, msg_index: usize) -> TokenStream {
+            self.from_words(ty, &Words::Registers(buffer_name, msg_index))
+        }
+
+        fn as_words(&self) -> Option<&dyn WordsType> {
+            Some(self)
+        }
+
+        fn is_type(&self, _: Type) -> bool {
+            false
+        }
+        fn consumes_register(&self) -> bool {
+            true
+        }
+        fn register_count(&self) -> usize {
+            self.layout().1
+        }
+        fn get_enum(&self) -> InputTypes {
+            InputTypes::TupleType(self.elems.clone(), self.widths.clone())
+        }
+    }
+
+    impl WordsType for TupleType {
+        fn to_words(&self, ident: Ident, ty: Type, words: &Words) -> TokenStream {
+            let elems = Self::elems(&ty);
+            let (layout, word_count) = self.layout();
+            let bindings: Vec<Ident> = (0..elems.len()).map(|k| format_ident!("{}_{}", ident, k)).collect();
+            let puts = (0..word_count).map(|word_index| {
+                let parts = elems.iter().zip(&self.elems).zip(&bindings).zip(&layout).zip(&self.widths)
+                    .filter(|((((_, _), _), (word, _)), _)| *word == word_index)
+                    .map(|((((elem_ty, elem_type), binding), (_, shift)), width)| {
+                        let word = element_word(elem_type).to_word(binding.clone(), elem_ty.clone());
+                        match width {
+                            Some(width) => {
+                                let mask = (1u64 << width) - 1;
+                                quote! { ((#word) & #mask) << #shift }
+                            },
+                            None => word
+                        }
+                    });
+                words.put(word_index, None, quote! { #(#parts)|* })
+            });
+            quote! {
+                {
+                    let (#(#bindings,)*) = #ident;
+                    #(#puts)*
+                }
+            }
+        }
+
+        fn from_words(&self, ty: Type, words: &Words) -> TokenStream {
+            let (layout, _) = self.layout();
+            let decodes = Self::elems(&ty).into_iter().zip(&self.elems).zip(layout).zip(&self.widths).map(|(((elem_ty, elem_type), (word, shift)), width)| {
+                let word = words.get(word, None);
+                let word_type = element_word(elem_type);
+                match width {
+                    Some(width) => unpack_element(word_type.as_ref(), &elem_ty, word, quote! { #shift }, *width),
+                    None => word_type.from_word(elem_ty, word)
+                }
+            });
+            quote! {
+                (#(#decodes,)*)
+            }
+        }
+    }
+
+    // `&mut T` out-parameter for a scalar T, sent back in the reply, see
+    // OutputParam. the server's handler writes into a default value
+    struct OutType {}
+    impl InputType for OutType {
+        fn into_ipc_buf(&self, _: Ident, _: Type, _: Ident, _: usize) -> TokenStream {
+            quote! {}
+        }
+
+        fn from_ipc_buf(&self, _: Type, _: Ident, _: usize) -> TokenStream {
+            quote! { &mut Default::default() }
+        }
+
+        fn is_type(&self, ty: Type) -> bool {
+            match out_param_inner(&ty) {
+                // the server starts the out-parameter from Default::default()
+                Some(inner) => has_default(&inner) && matches!(match_type(inner), Ok(InputTypes::NumberType | InputTypes::BoolType)),
+                None => false
+            }
+        }
+        fn consumes_register(&self) -> bool {
+            false
+        }
+        fn get_enum(&self) -> InputTypes {
+            InputTypes::OutType
+        }
+    }
+
+    // pointers and NonZero* are the numbers without a Default
+    fn has_default(ty: &Type) -> bool {
+        match ty {
+            Type::Ptr(_) => false,
+            Type::Path(type_path) => type_path.path.segments
+                .last()
+                .is_none_or(|seg| !seg.ident.to_string().starts_with("NonZero")),
+            _ => true
+        }
+    }
+
+    // LocalHandle<T> -> T
+    pub fn handle_inner(ty: &Type) -> Option<Type> {
+        if let Type::Path(ref type_path) = ty {
+            if let Some(seg) = type_path.path.segments.last() {
+                if let (true, PathArguments::AngleBracketed(ref bracketed_args)) = (seg.ident == "LocalHandle", &seg.arguments) {
+                    if let (1, Some(GenericArgument::Type(inner))) = (bracketed_args.args.len(), bracketed_args.args.first()) {
+                        return Some(inner.clone());
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // &mut T -> T
+    pub fn out_param_inner(ty: &Type) -> Option<Type> {
+        match ty {
+            Type::Reference(reference) if reference.mutability.is_some() => Some(reference.elem.as_ref().clone()),
+            _ => None
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn tuple_elements_share_a_word_while_they_fit() {
+            let tuple = TupleType { elems: vec![InputTypes::NumberType; 6], widths: vec![Some(8), Some(32), None, Some(1), Some(32), Some(32)] };
+            let (layout, words) = tuple.layout();
+            assert_eq!(layout, [(0, 0), (0, 8), (1, 0), (2, 0), (2, 1), (3, 0)]);
+            assert_eq!(words, 4);
+        }
+
+        #[test]
+        fn tuple_of_full_words_takes_one_each() {
+            let tuple = TupleType { elems: vec![InputTypes::NumberType; 2], widths: vec![None, None] };
+            assert_eq!(tuple.layout(), (vec![(0, 0), (1, 0)], 2));
+        }
+
+        #[test]
+        fn array_packs_elements_by_width() {
+            let elem = Box::new(InputTypes::NumberType);
+            assert_eq!(ArrayType { len: 17, elem: elem.clone(), width: Some(8) }.register_count(), 3);
+            assert_eq!(ArrayType { len: 3, elem: elem.clone(), width: None }.register_count(), 3);
+            assert_eq!(ArrayType { len: 0, elem, width: Some(1) }.register_count(), 0);
+        }
+
+        #[test]
+        fn optionals_see_the_declared_types() {
+            let mut context = TypeContext::default();
+            context.add_enum(&parse_quote! { enum Kind { A, B } });
+            let input_type = match_type_in(parse_quote! { Option<Kind> }, &context);
+            assert!(matches!(input_type, Ok(InputTypes::OptionType(inner)) if matches!(*inner, InputTypes::EnumType(_))));
+            assert!(match_type_in(parse_quote! { Option<Kind> }, &TypeContext::default()).is_err());
+            assert!(match_type_in(parse_quote! { Option<Option<Kind>> }, &context).is_err());
+        }
+
+        #[test]
+        fn elements_see_the_declared_types() {
+            let mut context = TypeContext::default();
+            context.add_enum(&parse_quote! { enum Kind { A = 1, B = 2 } });
+            let tuple = match_type_in(parse_quote! { (Kind, u8) }, &context);
+            assert!(matches!(tuple, Ok(InputTypes::TupleType(_, widths)) if widths == [Some(2), Some(8)]));
+            // arrays decode into defaults, which an IDL enum needn't have
+            assert!(match_type_in(parse_quote! { [Kind; 2] }, &context).is_err());
+            assert!(match_type_in(parse_quote! { (Kind, u8) }, &TypeContext::default()).is_err());
+        }
+
+        #[test]
+        fn out_params_start_from_a_default() {
+            assert!(OutType {}.is_type(parse_quote! { &mut u64 }));
+            assert!(OutType {}.is_type(parse_quote! { &mut char }));
+            assert!(!OutType {}.is_type(parse_quote! { &mut NonZeroU64 }));
+            assert!(!OutType {}.is_type(parse_quote! { &mut core::num::NonZeroU8 }));
+            assert!(!OutType {}.is_type(parse_quote! { &mut *const u8 }));
+        }
+    }
+}
@@ -0,0 +1,18 @@
+#![allow(clippy::module_inception)]
+pub mod builder;
+pub mod errors;
+pub mod input_node;
+pub mod method_node;
+pub mod output_node;
+pub mod trait_node;
+pub mod type_context;
+
+// seL4_MsgMaxLength, the number of message registers in the IPC buffer
+pub const MSG_MAX_LENGTH: usize = 120;
+// seL4_MsgMaxExtraCaps, the number of caps one message can carry
+pub const MSG_MAX_EXTRA_CAPS: usize = 3;
+// seL4_MsgLabelBits, labels have to be below 1 << 52
+pub const MAX_LABEL: u64 = 1 << 52;
+
+pub use builder::builder::{Builder, Verbosity};
+pub use type_context::type_context::TypeContext;
@@ -0,0 +1,869 @@
+pub mod method_node {
+    use proc_macro2::TokenStream;
+    use quote::{format_ident, quote};
+    use quote::ToTokens;
+    use syn::{parse_quote, punctuated::Punctuated, spanned::Spanned, token::Comma, Attribute, FnArg, Ident, Pat, PatType, ReturnType, TraitItemFn, Type};
+    use crate::errors::errors;
+    use crate::input_node::input_node::{generated_ident, get_input_param, out_param_inner, InputParam, InputTypes, RESERVED_PREFIX};
+    use crate::trait_node::trait_node::IdlOptions;
+    use crate::type_context::type_context::{strip_lifetimes, TypeContext, MAX_NESTING};
+    use crate::output_node::output_node::{get_output_param, split_result, wrap_result, OutputParam};
+
+    // attributes that only mean something to the generator
+    pub fn is_idl_attr(attr: &Attribute) -> bool {
+        ["label", "idl", "send", "recv"].iter().any(|name| attr.path().is_ident(name))
+    }
+
+    // the IDL may spell out a receiver or not, the generated code always takes &self
+    fn idl_params(params: &Punctuated<FnArg, Comma>) -> Punctuated<FnArg, Comma> {
+        params
+            .iter()
+            .filter(|p| matches!(p, FnArg::Typed(_)))
+            .cloned()
+            .collect()
+    }
+
+    fn map_param_types(params: &Punctuated<FnArg, Comma>, f: impl Fn(&Type) -> Type) -> Punctuated<FnArg, Comma> {
+        params
+            .iter()
+            .map(|p| match p {
+                FnArg::Typed(pat_type) => FnArg::Typed(PatType { ty: Box::new(f(&pat_type.ty)), ..pat_type.clone() }),
+                p => p.clone()
+            })
+            .collect()
+    }
+
+    fn map_return_type(return_type: &ReturnType, f: impl Fn(&Type) -> Type) -> ReturnType {
+        match return_type {
+            ReturnType::Type(arrow, ty) => ReturnType::Type(*arrow, Box::new(f(ty))),
+            ReturnType::Default => ReturnType::Default
+        }
+    }
+
+    // the parameters as written out in generated signatures
+    fn typed_params(params: &Punctuated<FnArg, Comma>) -> Punctuated<FnArg, Comma> {
+        map_param_types(&idl_params(params), strip_lifetimes)
+            .into_iter()
+            .map(|p| match p {
+                FnArg::Typed(mut pat_type) => {
+                    pat_type.attrs.retain(|a| !is_idl_attr(a));
+                    FnArg::Typed(pat_type)
+                },
+                p => p
+            })
+            .collect()
+    }
+
+    // the return type as written out in generated signatures, and the
+    // IDL's own error type if it returns a Result
+    fn signature_return(output: &ReturnType) -> (ReturnType, Option<Type>) {
+        let (return_type, error_type) = split_result(output.clone());
+        (map_return_type(&return_type, strip_lifetimes), error_type.map(|ty| strip_lifetimes(&ty)))
+    }
+
+    // a parameter of an IDL struct type, sent as one parameter per field,
+    // and so on down nested structs. fields get generated names numbered
+    // across the method, so they can't clash with each other or a parameter
+    struct StructParam {
+        ident: Ident,
+        ty: Type,
+        // (field, generated name, the field's own fields if it's a struct)
+        fields: Vec<(Ident, Ident, Option<StructParam>)>,
+    }
+
+    impl StructParam {
+        // splits the struct into its fields, appending them to flat_params
+        fn new(ident: Ident, ty: Type, context: &TypeContext, flat_params: &mut Punctuated<FnArg, Comma>, counter: &mut usize, depth: usize) -> Result<Option<Self>, errors::Error> {
+            let fields = match context.struct_fields(&context.resolve(&ty)) {
+                Some(fields) => fields,
+                None => return Ok(None)
+            };
+            if depth > MAX_NESTING {
+                return Err(errors::Error::UnsupportedType(ty.to_token_stream().to_string(), ty.span()));
+            }
+            let mut struct_fields = vec![];
+            for (field, field_ty) in fields {
+                // a struct without lifetimes can't hold borrowed data from the message
+                if let Type::Reference(_) = context.resolve(&field_ty) {
+                    return Err(errors::Error::UnsupportedType(field_ty.to_token_stream().to_string(), field_ty.span()));
+                }
+                let field_ident = generated_ident(format!("{}_{}", field, counter));
+                *counter += 1;
+                let nested = Self::new(field_ident.clone(), field_ty.clone(), context, flat_params, counter, depth + 1)?;
+                if nested.is_none() {
+                    flat_params.push(parse_quote! { #field_ident: #field_ty });
+                }
+                struct_fields.push((field, field_ident, nested));
+            }
+            Ok(Some(StructParam { ident, ty, fields: struct_fields }))
+        }
+
+        // client side, moves the fields out into their own variables
+        fn destructure(&self) -> TokenStream {
+            let (ident, ty) = (&self.ident, &self.ty);
+            let names = self.fields.iter().map(|(field, _, _)| field);
+            let idents = self.fields.iter().map(|(_, ident, _)| ident);
+            let nested = self.fields.iter().filter_map(|(_, _, nested)| nested.as_ref().map(|n| n.destructure()));
+            quote! {
+                let #ty { #(#names: #idents),* } = #ident;
+                #(#nested)*
+            }
+        }
+
+        // server side, puts the decoded fields back together
+        fn rebuild(&self) -> TokenStream {
+            let (ident, ty) = (&self.ident, &self.ty);
+            let names = self.fields.iter().map(|(field, _, _)| field);
+            let idents = self.fields.iter().map(|(_, ident, _)| ident);
+            let nested = self.fields.iter().filter_map(|(_, _, nested)| nested.as_ref().map(|n| n.rebuild()));
+            quote! {
+                #(#nested)*
+                let #ident = #ty { #(#names: #idents),* };
+            }
+        }
+    }
+
+    fn mock_field(ident: &Ident, params: &Punctuated<FnArg, Comma>, return_type: &TokenStream) -> TokenStream {
+        let param_types = params.iter().filter_map(|p| match p {
+            FnArg::Typed(PatType { ty, .. }) => Some(ty),
+            _ => None
+        });
+        quote! {
+            pub #ident: Option<Box<dyn Fn(#(#param_types),*) #return_type>>,
+        }
+    }
+
+    // forwards to the closure in the field of the same name, if one is set
+    fn mock_method(ident: &Ident, params: &Punctuated<FnArg, Comma>, return_type: &TokenStream) -> TokenStream {
+        let args = params.iter().filter_map(|p| match p {
+            FnArg::Typed(PatType { pat, .. }) => Some(pat),
+            _ => None
+        });
+        quote! {
+            fn #ident(&self, #params) #return_type {
+                match &self.#ident {
+                    Some(__idl_f) => __idl_f(#(#args),*),
+                    None => Err(smos_common::error::InvocationError::InvalidInvocation.into()),
+                }
+            }
+        }
+    }
+
+    pub struct MethodNode {
+        attrs: Vec<Attribute>,
+        ident: Ident,
+        params: Punctuated<FnArg, Comma>,
+        input_params: Vec<InputParam>,
+        return_type: ReturnType,
+        error_type: Type,
+        output_param: OutputParam,
+        has_buffer: bool,
+        // registers at the start of the message shared by packed parameters
+        packed_registers: usize,
+        // parameters that didn't fit the message registers
+        spilled: usize,
+        // `#[send]` caps, in caps_or_badges order
+        sent_caps: usize,
+        // parameters sent field by field
+        struct_params: Vec<StructParam>,
+    }
+
+    impl MethodNode {
+        // the trait's error type is used unless the IDL method already returns a Result.
+        // generated code spells types as written, the layout works on resolved ones
+        pub fn new(method: TraitItemFn, options: &IdlOptions, context: &TypeContext) -> Result<Self, errors::Error> {
+            let written_params = map_param_types(&idl_params(&method.sig.inputs), strip_lifetimes);
+            let written_return = map_return_type(&split_result(method.sig.output.clone()).0, strip_lifetimes);
+            let mut handle_errors = vec![];
+            for p in &written_params {
+                if let FnArg::Typed(PatType { ty, .. }) = p {
+                    handle_errors.extend(context.check_handles(&context.resolve(ty)).err());
+                }
+            }
+            if let ReturnType::Type(_, ty) = &written_return {
+                handle_errors.extend(context.check_handles(&context.resolve(ty)).err());
+            }
+            if let Some(e) = errors::Error::combine(handle_errors) {
+                return Err(e);
+            }
+            let attrs = method.attrs.into_iter().filter(|a| !is_idl_attr(a)).collect();
+            let ident = method.sig.ident;
+            let params = typed_params(&method.sig.inputs);
+            let (return_type, method_error_type) = signature_return(&method.sig.output);
+            let error_type = method_error_type.unwrap_or(options.error_type());
+            let (flat_params, struct_params) = Self::flatten_params(written_params, context)?;
+            let (sanitised, has_buffer, packed_registers) = Self::sanitise_params(flat_params, options.pack, context)?;
+            let out_params = sanitised
+                .iter()
+                .filter(|p| matches!(p.input_type, InputTypes::OutType))
+                .filter_map(|p| Some((format_ident!("{}", p.ident), out_param_inner(p.ty())?)))
+                .collect();
+            let recv_slot = sanitised
+                .iter()
+                .find(|p| matches!(p.input_type, InputTypes::AbsoluteCptrType))
+                .map(|p| format_ident!("{}", p.ident));
+            let output_param = get_output_param(written_return, out_params, recv_slot.clone(), context)?;
+            if output_param.returns_cap() && recv_slot.is_none() {
+                return Err(errors::Error::NoRecvSlot(ident.to_string(), return_type.span()));
+            }
+            // the reply has no spill area
+            if output_param.register_count() > crate::MSG_MAX_LENGTH {
+                return Err(errors::Error::TooManyRegisters(ident.to_string(), output_param.register_count(), ident.span()));
+            }
+            let mut method_node = MethodNode { 
+                attrs,
+                ident, 
+                params, 
+                return_type, 
+                error_type,
+                input_params: sanitised, 
+                output_param,
+                has_buffer,
+                packed_registers,
+                spilled: 0,
+                sent_caps: 0,
+                struct_params
+            };
+            method_node.assign_caps()?;
+            method_node.assign_registers(options.strict)?;
+            Ok(method_node)
+        }
+
+        // sent caps take the extra cap slots in declaration order, while
+        // everything received has to share the one receive slot
+        fn assign_caps(&mut self) -> Result<(), errors::Error> {
+            if self.input_params.iter().filter(|p| p.is_recv_slot()).count() > 1 {
+                return Err(errors::Error::MultipleRecvSlots(self.ident.to_string(), self.ident.span()));
+            }
+            let sent = self.input_params
+                .iter_mut()
+                .filter(|p| matches!(p.input_type, InputTypes::SendCapType(_)));
+            for (cap_index, input_param) in sent.enumerate() {
+                input_param.input_type = InputTypes::SendCapType(cap_index);
+                self.sent_caps += 1;
+            }
+            if self.sent_caps > crate::MSG_MAX_EXTRA_CAPS {
+                return Err(errors::Error::TooManyCaps(self.ident.to_string(), self.sent_caps, self.ident.span()));
+            }
+            Ok(())
+        }
+
+        // replaces every IDL struct parameter by its fields, reporting every
+        // parameter that can't be
+        fn flatten_params(params: Punctuated<FnArg, Comma>, context: &TypeContext) -> Result<(Punctuated<FnArg, Comma>, Vec<StructParam>), errors::Error> {
+            let mut flat_params = Punctuated::new();
+            let mut struct_params = vec![];
+            let mut errors = vec![];
+            let mut counter = 0;
+            for p in params {
+                if let FnArg::Typed(PatType { ref pat, ref ty, .. }) = p {
+                    if let Pat::Ident(ref pat_ident) = **pat {
+                        if pat_ident.ident.to_string().starts_with(RESERVED_PREFIX) {
+                            errors.push(errors::Error::ReservedName(pat_ident.ident.to_string(), pat_ident.ident.span()));
+                            continue;
+                        }
+                        match StructParam::new(pat_ident.ident.clone(), ty.as_ref().clone(), context, &mut flat_params, &mut counter, 0) {
+                            Ok(Some(struct_param)) => {
+                                struct_params.push(struct_param);
+                                continue;
+                            },
+                            Ok(None) => {},
+                            Err(e) => {
+                                errors.push(e);
+                                continue;
+                            }
+                        }
+                    }
+                }
+                flat_params.push(p);
+            }
+            match errors::Error::combine(errors) {
+                Some(e) => Err(e),
+                None => Ok((flat_params, struct_params))
+            }
+        }
+
+        // with pack, small scalars share the first registers, first fit in
+        // declaration order. optionals go last. every parameter that doesn't
+        // match a type is reported, not just the first
+        pub fn sanitise_params(params: Punctuated<FnArg, Comma>, pack: bool, context: &TypeContext) -> Result<(Vec<InputParam>, bool, usize), errors::Error> {
+            let mut sanitised = vec![];
+            let mut optional_register = vec![];
+            let mut has_buffer = false;
+            // bits used in each packed register
+            let mut packed: Vec<u32> = vec![];
+            let mut errors = vec![];
+            for p in params {
+                let mut input_param = match get_input_param(p, context) {
+                    Ok(input_param) => input_param,
+                    Err(e) => {
+                        errors.push(e);
+                        continue;
+                    }
+                };
+                if let (true, Some(width)) = (pack, input_param.bit_width()) {
+                    let register = match packed.iter().position(|used| used + width <= 64) {
+                        Some(register) => register,
+                        None => {
+                            packed.push(0);
+                            packed.len() - 1
+                        }
+                    };
+                    input_param.packed = Some((register, packed[register]));
+                    packed[register] += width;
+                    sanitised.push(input_param);
+                    continue;
+                }
+                match input_param.input_type {
+                    InputTypes::OptionType(_) => {
+                        optional_register.push(input_param);
+                    },
+                    InputTypes::BufferType => {
+                        sanitised.push(input_param);
+                        has_buffer = true;
+                    },
+                    _ => {
+                        sanitised.push(input_param);
+                    }
+                }
+            }
+            if let Some(e) = errors::Error::combine(errors) {
+                return Err(e);
+            }
+            sanitised.append(&mut optional_register);
+            Ok((sanitised, has_buffer, packed.len()))
+        }
+
+        // after the packed registers comes the presence register, with one bit
+        // per optional, then every other parameter in order. once the registers
+        // run out, the rest spill to the shared buffer and the last register
+        // records where, unless strict asks for an error instead. parameters
+        // that can't spill are never crowded out by ones declared before them
+        fn assign_registers(&mut self, strict: bool) -> Result<(), errors::Error> {
+            let optionals = self.input_params
+                .iter()
+                .filter(|p| matches!(p.input_type, InputTypes::OptionType(_)))
+                .count();
+            if optionals > 64 {
+                return Err(errors::Error::TooManyOptionals(self.ident.to_string(), self.ident.span()));
+            }
+            let presence_register = self.packed_registers;
+            let first_register = self.first_register();
+            let optional_params = self.input_params
+                .iter_mut()
+                .filter(|p| matches!(p.input_type, InputTypes::OptionType(_)));
+            for (bit, input_param) in optional_params.enumerate() {
+                input_param.presence = Some((presence_register, bit));
+            }
+
+            let needed = first_register + self.input_params.iter().map(|p| p.register_count()).sum::<usize>();
+            if needed <= crate::MSG_MAX_LENGTH {
+                return Ok(());
+            }
+            if strict {
+                return Err(errors::Error::TooManyRegisters(self.ident.to_string(), needed, self.ident.span()));
+            }
+            // whatever can't spill gets its registers first, wherever it is declared
+            let pinned: usize = self.input_params
+                .iter()
+                .filter(|p| !p.spills())
+                .map(|p| p.register_count())
+                .sum();
+            let mut free = (crate::MSG_MAX_LENGTH - 1)
+                .checked_sub(first_register + pinned)
+                .ok_or_else(|| errors::Error::TooManyRegisters(self.ident.to_string(), needed, self.ident.span()))?;
+            for input_param in self.input_params.iter_mut().filter(|p| p.register_count() > 0 && p.spills()) {
+                if input_param.register_count() <= free {
+                    free -= input_param.register_count();
+                } else {
+                    input_param.spilled = Some(self.spilled);
+                    self.spilled += input_param.register_count();
+                }
+            }
+            Ok(())
+        }
+
+        // the first register of the unpacked parameters
+        fn first_register(&self) -> usize {
+            let has_optionals = self.input_params.iter().any(|p| matches!(p.input_type, InputTypes::OptionType(_)));
+            self.packed_registers + has_optionals as usize
+        }
+
+        pub fn uses_shared_buffer(&self) -> bool {
+            self.has_buffer || self.spilled > 0
+        }
+
+        pub fn to_method(&self, ipc_buffer_name: String, msg_name: String, label: TokenStream) -> TokenStream {
+            let marshal_code = self.marshal_code(ipc_buffer_name, msg_name, label);
+            let method_ident = self.ident.clone();
+            let method_params = self.params.clone();
+            let method_return_type = wrap_result(&self.return_type, &self.error_type);
+            let shared_buffer_code = if self.uses_shared_buffer() {
+                Self::get_shared_buffer_code()
+            } else {
+                quote! {}
+            };
+            let layout_code = self.get_buffer_layout_code();
+            let destructure = self.struct_params.iter().map(|s| s.destructure());
+            quote! {
+                fn #method_ident(&self, #method_params) #method_return_type {
+                    #(#destructure)*
+                    #shared_buffer_code
+                    #layout_code
+                    #marshal_code
+                }
+            }
+        }
+
+        pub fn to_trait_decl(&self) -> TokenStream {
+            let method_attrs = &self.attrs;
+            let method_ident = self.ident.clone();
+            let method_params = self.params.clone();
+            let method_return_type = wrap_result(&self.return_type, &self.error_type);
+            quote! {
+                #(#method_attrs)*
+                fn #method_ident(&self, #method_params) #method_return_type;
+            }
+        }
+
+        fn get_shared_buffer_code() -> TokenStream {
+            let raw = Self::get_shared_buffer_raw_code();
+            quote! {
+                #raw
+                let __idl_shared_buf =
+                    unsafe { core::slice::from_raw_parts_mut(__idl_shared_buf_raw.0, __idl_shared_buf_raw.1) };
+            }
+        }
+
+        // the server never makes a slice of the whole buffer, every argument is
+        // its own slice off the pointer so a mutable one doesn't alias another
+        fn get_shared_buffer_raw_code() -> TokenStream {
+            quote! {
+                let __idl_shared_buf_raw = self
+                    .get_buf_mut()
+                    .ok_or(smos_common::error::InvocationError::DataBufferNotSet)?;
+            }
+        }
+
+        // every variable-length argument gets the next free bytes of the shared
+        // buffer, and the spill area follows 8 byte aligned
+        fn get_buffer_layout_code(&self) -> TokenStream {
+            if !self.uses_shared_buffer() {
+                return quote! {};
+            }
+            let offsets = self.input_params
+                .iter()
+                .filter(|p| matches!(p.input_type, InputTypes::BufferType))
+                .map(|p| {
+                    let ident = format_ident!("{}", p.ident);
+                    let offset = generated_ident(format!("{}_offset", p.ident));
+                    quote! {
+                        let #offset = __idl_shared_cursor;
+                        __idl_shared_cursor += #ident.len();
+                    }
+                });
+            let spill = if self.spilled > 0 {
+                let spill_len = 8 * self.spilled;
+                quote! {
+                    let __idl_spill_offset = __idl_shared_cursor.next_multiple_of(8);
+                    __idl_shared_cursor = __idl_spill_offset + #spill_len;
+                }
+            } else {
+                quote! {}
+            };
+            quote! {
+                let mut __idl_shared_cursor = 0usize;
+                #(#offsets)*
+                #spill
+                if __idl_shared_cursor > __idl_shared_buf.len() {
+                    return Err(smos_common::error::InvocationError::InvalidArguments.into());
+                }
+            }
+        }
+
+        // the spill area as announced by the client, checked against the buffer
+        fn get_spill_read_code(&self, ipc_buffer_name: &Ident) -> TokenStream {
+            if self.spilled == 0 {
+                return quote! {};
+            }
+            let offset_register = crate::MSG_MAX_LENGTH - 1;
+            let spill_len = 8 * self.spilled;
+            quote! {
+                let __idl_spill_offset = #ipc_buffer_name.msg_regs()[#offset_register] as usize;
+                if __idl_spill_offset.checked_add(#spill_len).filter(|end| *end <= __idl_shared_buf_raw.1).is_none() {
+                    return Err(smos_common::error::InvocationError::InvalidArguments);
+                }
+                let __idl_spill: &[u8] =
+                    unsafe { core::slice::from_raw_parts(__idl_shared_buf_raw.0.add(__idl_spill_offset), #spill_len) };
+                let __idl_spill_word = |i: usize| {
+                    let mut word = [0u8; 8];
+                    word.copy_from_slice(&__idl_spill[8 * i..][..8]);
+                    u64::from_ne_bytes(word)
+                };
+            }
+        }
+
+        // a mutable buffer may not share bytes with any other buffer, or with
+        // the spill area
+        fn get_overlap_check_code(&self, ipc_buffer_name: &Ident) -> TokenStream {
+            if !self.input_params.iter().any(|p| p.is_mut_buffer()) {
+                return quote! {};
+            }
+            let mut ranges = vec![];
+            let mut msg_index = self.first_register();
+            for param in self.input_params.iter().filter(|p| p.spilled.is_none()) {
+                if matches!(param.input_type, InputTypes::BufferType) {
+                    let len_index = msg_index + 1;
+                    let is_mut = param.is_mut_buffer();
+                    ranges.push(quote! {
+                        (#ipc_buffer_name.msg_regs()[#msg_index], #ipc_buffer_name.msg_regs()[#len_index], #is_mut)
+                    });
+                }
+                msg_index += param.register_count();
+            }
+            if self.spilled > 0 {
+                let spill_len = 8 * self.spilled as u64;
+                ranges.push(quote! { (__idl_spill_offset as u64, #spill_len, false) });
+            }
+            quote! {
+                let __idl_ranges = [#(#ranges),*];
+                for (i, a) in __idl_ranges.iter().enumerate() {
+                    for b in &__idl_ranges[i + 1..] {
+                        if (a.2 || b.2) && a.0 < b.0.saturating_add(b.1) && b.0 < a.0.saturating_add(a.1) {
+                            return Err(smos_common::error::InvocationError::InvalidArguments);
+                        }
+                    }
+                }
+            }
+        }
+
+        pub fn marshal_code(&self, ipc_buffer_name: String, msg_name: String, label: TokenStream) -> TokenStream {
+            let (marshalls, msg_len) = self.marshal_all_inputs(ipc_buffer_name.clone());
+            let unmarshall = self.unmarshal_output(ipc_buffer_name.clone());
+            let copy_outs = self.input_params.iter().map(|p| p.get_copy_out_code());
+            let out_params = self.output_param.get_out_param_code(ipc_buffer_name.clone());
+            let cap_check = self.output_param.get_cap_check_code();
+            let extra_caps = if self.sent_caps > 0 {
+                let sent_caps = self.sent_caps;
+                quote! { .extra_caps(#sent_caps) }
+            } else {
+                quote! {}
+            };
+            let ipc_buffer_name = format_ident!("{}", ipc_buffer_name);
+            let msg_name = format_ident!("{}", msg_name);
+            quote! {
+                sel4::with_ipc_buffer_mut(|#ipc_buffer_name| {
+                    #(#marshalls)*
+                    Ok::<(), smos_common::error::InvocationError>(())
+                })?;
+                let #msg_name = sel4::MessageInfoBuilder::default()
+                    .label(#label as u64)
+                    .length(#msg_len)
+                    #extra_caps
+                    .build();
+                let __idl_reply = self.ep().call(#msg_name);
+                sel4::with_ipc_buffer(|#ipc_buffer_name| {
+                    smos_common::error::try_unpack_error(__idl_reply.label(), #ipc_buffer_name)?;
+                    #cap_check
+                    #(#copy_outs)*
+                    #out_params
+                    Ok(#unmarshall)
+                })
+            }
+        }
+
+        pub fn to_handler_decl(&self) -> TokenStream {
+            let method_ident = self.ident.clone();
+            let handler_params = self.handler_params().into_iter().map(|(ident, ty)| quote! { #ident: #ty });
+            let handler_return_type = self.output_param.get_handler_type();
+            quote! {
+                fn #method_ident(&mut self, #(#handler_params),*) -> #handler_return_type;
+            }
+        }
+
+        // the IDL parameters in declaration order, typed as the server sees them
+        fn handler_params(&self) -> Vec<(Ident, Type)> {
+            self.params
+                .iter()
+                .filter_map(|p| {
+                    if let FnArg::Typed(PatType { pat, .. }) = p {
+                        if let Pat::Ident(pat_ident) = pat.as_ref() {
+                            if let Some(s) = self.struct_params.iter().find(|s| pat_ident.ident == s.ident) {
+                                return Some((s.ident.clone(), s.ty.clone()));
+                            }
+                            return self.input_params
+                                .iter()
+                                .find(|i| pat_ident.ident == i.ident)
+                                .and_then(|i| i.handler_type())
+                                .map(|ty| (pat_ident.ident.clone(), ty));
+                        }
+                    }
+                    None
+                })
+                .collect()
+        }
+
+        pub fn to_mock_field(&self) -> TokenStream {
+            mock_field(&self.ident, &self.params, &wrap_result(&self.return_type, &self.error_type))
+        }
+
+        pub fn to_mock_method(&self) -> TokenStream {
+            mock_method(&self.ident, &self.params, &wrap_result(&self.return_type, &self.error_type))
+        }
+
+        // stubbed methods can still be mocked, their types never reach a message
+        pub fn to_unimplemented_mock_field(method: &TraitItemFn, error_type: Type) -> TokenStream {
+            let params = typed_params(&method.sig.inputs);
+            let (return_type, method_error_type) = signature_return(&method.sig.output);
+            mock_field(&method.sig.ident, &params, &wrap_result(&return_type, &method_error_type.unwrap_or(error_type)))
+        }
+
+        pub fn to_unimplemented_mock_method(method: &TraitItemFn, error_type: Type) -> TokenStream {
+            let params = typed_params(&method.sig.inputs);
+            let (return_type, method_error_type) = signature_return(&method.sig.output);
+            mock_method(&method.sig.ident, &params, &wrap_result(&return_type, &method_error_type.unwrap_or(error_type)))
+        }
+
+        pub fn to_dispatch_arm(&self, ipc_buffer_name: String, msg_name: String, label: TokenStream) -> TokenStream {
+            let (unmarshalls, msg_len) = self.unmarshal_all_inputs(ipc_buffer_name.clone());
+            let mut reply_len = 0;
+            let ret = generated_ident("ret");
+            let marshall = self.output_param.get_marshal_code(ret.to_string(), ipc_buffer_name.clone(), &mut reply_len);
+            let reply_info = self.output_param.get_reply_info_code(reply_len);
+            let msg_name = format_ident!("{}", msg_name);
+            let method_ident = self.ident.clone();
+            let args = self.handler_params().into_iter().map(|(ident, _)| ident);
+            let shared_buffer_code = if self.uses_shared_buffer() {
+                Self::get_shared_buffer_raw_code()
+            } else {
+                quote! {}
+            };
+            let spill_code = self.get_spill_read_code(&format_ident!("{}", ipc_buffer_name));
+            let overlap_check = self.get_overlap_check_code(&format_ident!("{}", ipc_buffer_name));
+            let rebuild = self.struct_params.iter().map(|s| s.rebuild());
+            let call = if self.output_param.is_unit() {
+                quote! { self.#method_ident(#(#args),*)?; }
+            } else {
+                quote! { let #ret = self.#method_ident(#(#args),*)?; }
+            };
+            let length_check = if msg_len > 0 {
+                quote! {
+                    if #msg_name.length() < #msg_len {
+                        return Err(smos_common::error::InvocationError::InvalidArguments);
+                    }
+                }
+            } else {
+                quote! {}
+            };
+            let caps_check = if self.sent_caps > 0 {
+                let sent_caps = self.sent_caps;
+                quote! {
+                    if #msg_name.extra_caps() < #sent_caps {
+                        return Err(smos_common::error::InvocationError::InvalidArguments);
+                    }
+                    let __idl_caps_unwrapped = #msg_name.caps_unwrapped();
+                }
+            } else {
+                quote! {}
+            };
+            quote! {
+                #label => {
+                    #length_check
+                    #caps_check
+                    #shared_buffer_code
+                    #spill_code
+                    #overlap_check
+                    #(#unmarshalls)*
+                    #(#rebuild)*
+                    #call
+                    #marshall
+                    Ok(#reply_info.build())
+                }
+            }
+        }
+
+        pub fn unmarshal_all_inputs(&self, buffer_name: String) -> (Vec<TokenStream>, usize) {
+            let mut unmarshalls = vec![];
+            let mut msg_index = self.first_register();
+            for param in &self.input_params {
+                unmarshalls.push(param.get_unmarshal_code(buffer_name.clone(), &mut msg_index));
+            }
+            if self.spilled > 0 {
+                msg_index = crate::MSG_MAX_LENGTH;
+            }
+            (unmarshalls, msg_index)
+        }
+
+        pub fn unmarshal_output(&self, buffer_name: String) -> TokenStream {
+            let mut msg_index = 0;
+            self.output_param.get_unmarshal_code(buffer_name, &mut msg_index)
+        }
+
+        pub fn marshal_all_inputs(&self, buffer_name: String) -> (Vec<TokenStream>, usize) {
+            let mut marshalls = vec![];
+            let ipc_buffer_name = format_ident!("{}", buffer_name);
+            // packed and presence registers are built up bit by bit
+            for register in 0..self.first_register() {
+                marshalls.push(quote! {
+                    #ipc_buffer_name.msg_regs_mut()[#register] = 0;
+                });
+            }
+            let mut msg_index = self.first_register();
+            for param in &self.input_params {
+                marshalls.push(param.get_marshal_code(buffer_name.clone(), &mut msg_index));
+            }
+            if self.spilled > 0 {
+                let offset_register = crate::MSG_MAX_LENGTH - 1;
+                marshalls.push(quote! {
+                    #ipc_buffer_name.msg_regs_mut()[#offset_register] = __idl_spill_offset as u64;
+                });
+                msg_index = crate::MSG_MAX_LENGTH;
+            }
+            (marshalls, msg_index)
+        }
+
+        pub fn to_unimplemented(method: TraitItemFn, error_type: Type) -> TokenStream {
+            let method_ident = method.sig.ident;
+            let method_params = typed_params(&method.sig.inputs);
+            let (return_type, method_error_type) = signature_return(&method.sig.output);
+            let method_return_type = wrap_result(&return_type, &method_error_type.unwrap_or(error_type));
+            quote! {
+                #[allow(unused_variables)]
+                fn #method_ident(&self, #method_params) #method_return_type {
+                    unimplemented!()
+                }
+            }
+        }
+
+        pub fn to_unimplemented_decl(method: TraitItemFn, error_type: Type) -> TokenStream {
+            let method_attrs = method.attrs.iter().filter(|a| !is_idl_attr(a));
+            let method_ident = method.sig.ident;
+            let method_params = typed_params(&method.sig.inputs);
+            let (return_type, method_error_type) = signature_return(&method.sig.output);
+            let method_return_type = wrap_result(&return_type, &method_error_type.unwrap_or(error_type));
+            quote! {
+                #(#method_attrs)*
+                fn #method_ident(&self, #method_params) #method_return_type;
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn method_node(method: TraitItemFn, pack: bool) -> MethodNode {
+            let options = IdlOptions { pack, ..Default::default() };
+            MethodNode::new(method, &options, &TypeContext::default()).unwrap_or_else(|e| panic!("{}", e))
+        }
+
+        fn param<'a>(method_node: &'a MethodNode, ident: &str) -> &'a InputParam {
+            method_node.input_params.iter().find(|p| p.ident == ident).unwrap()
+        }
+
+        #[test]
+        fn packs_first_fit_in_declaration_order() {
+            let method_node = method_node(parse_quote! {
+                fn f(a: u8, b: bool, c: u64, d: u32, e: Option<u16>, f: u32, g: i32);
+            }, true);
+            assert_eq!(method_node.packed_registers, 2);
+            assert_eq!(param(&method_node, "a").packed, Some((0, 0)));
+            assert_eq!(param(&method_node, "b").packed, Some((0, 8)));
+            assert_eq!(param(&method_node, "c").packed, None);
+            assert_eq!(param(&method_node, "d").packed, Some((0, 9)));
+            assert_eq!(param(&method_node, "e").packed, None);
+            assert_eq!(param(&method_node, "f").packed, Some((1, 0)));
+            assert_eq!(param(&method_node, "g").packed, Some((1, 32)));
+            // optionals go last
+            let order: Vec<&str> = method_node.input_params.iter().map(|p| p.ident.as_str()).collect();
+            assert_eq!(order, ["a", "b", "c", "d", "f", "g", "e"]);
+        }
+
+        #[test]
+        fn packs_nothing_without_pack() {
+            let method_node = method_node(parse_quote! {
+                fn f(a: u8, b: bool);
+            }, false);
+            assert_eq!(method_node.packed_registers, 0);
+            assert!(method_node.input_params.iter().all(|p| p.packed.is_none()));
+        }
+
+        // `fn f(prefix, x0: u64, .., x{n - 1}: u64, suffix)`
+        fn wide_method(prefix: &str, n: usize, suffix: &str) -> TraitItemFn {
+            let mut params = vec![prefix.to_string()];
+            params.extend((0..n).map(|i| format!("x{}: u64", i)));
+            params.push(suffix.to_string());
+            params.retain(|p| !p.is_empty());
+            syn::parse_str(&format!("fn f({});", params.join(", "))).unwrap()
+        }
+
+        #[test]
+        fn presence_register_follows_packed_registers() {
+            let method_node = method_node(parse_quote! {
+                fn f(a: u8, b: Option<bool>, c: u64, d: Option<u64>);
+            }, true);
+            assert_eq!(method_node.first_register(), 2);
+            assert_eq!(param(&method_node, "b").presence, Some((1, 0)));
+            assert_eq!(param(&method_node, "d").presence, Some((1, 1)));
+            assert_eq!(param(&method_node, "c").presence, None);
+        }
+
+        #[test]
+        fn spills_in_declaration_order_once_registers_run_out() {
+            // the presence register and the spill offset register leave 118
+            let method_node = method_node(wide_method("a: Option<u64>", 119, ""), false);
+            assert_eq!(method_node.first_register(), 1);
+            assert_eq!(param(&method_node, "a").presence, Some((0, 0)));
+            assert_eq!(param(&method_node, "x117").spilled, None);
+            assert_eq!(param(&method_node, "x118").spilled, Some(0));
+            // optionals go last, so spill last
+            assert_eq!(param(&method_node, "a").spilled, Some(1));
+            assert_eq!(method_node.spilled, 2);
+        }
+
+        #[test]
+        fn nothing_spills_when_everything_fits() {
+            let method_node = method_node(wide_method("", crate::MSG_MAX_LENGTH, ""), false);
+            assert_eq!(method_node.spilled, 0);
+        }
+
+        #[test]
+        fn buffers_keep_their_registers_when_declared_last() {
+            let method_node = method_node(wide_method("", 125, "s: &str"), false);
+            assert_eq!(param(&method_node, "s").spilled, None);
+            assert_eq!(param(&method_node, "x116").spilled, None);
+            assert_eq!(param(&method_node, "x117").spilled, Some(0));
+            assert_eq!(param(&method_node, "x124").spilled, Some(7));
+            assert_eq!(method_node.spilled, 8);
+        }
+
+        #[test]
+        fn strict_refuses_to_spill() {
+            let options = IdlOptions { strict: true, ..Default::default() };
+            let result = MethodNode::new(wide_method("", 121, ""), &options, &TypeContext::default());
+            assert!(matches!(result, Err(errors::Error::TooManyRegisters(_, 121, _))));
+        }
+
+        #[test]
+        fn reports_every_bad_param() {
+            let method: TraitItemFn = parse_quote! {
+                fn f(a: Option<Option<u8>>, b: u8, c: Vec<String>);
+            };
+            let result = MethodNode::new(method, &IdlOptions::default(), &TypeContext::default());
+            let messages: Vec<String> = match result {
+                Err(errors::Error::Multiple(errors)) => errors.iter().map(|e| e.to_string()).collect(),
+                _ => panic!("expected an error for each bad parameter"),
+            };
+            assert_eq!(messages, ["unsupported type `Option<Option<u8>>`", "unsupported type `Vec<String>`"]);
+        }
+
+        #[test]
+        fn reply_has_no_spill_area() {
+            let fits: TraitItemFn = syn::parse_str(&format!("fn f() -> ({});", "u64, ".repeat(crate::MSG_MAX_LENGTH))).unwrap();
+            assert!(MethodNode::new(fits, &IdlOptions::default(), &TypeContext::default()).is_ok());
+            let too_wide: TraitItemFn = syn::parse_str(&format!("fn f(out: &mut u64) -> ({});", "u64, ".repeat(crate::MSG_MAX_LENGTH))).unwrap();
+            let result = MethodNode::new(too_wide, &IdlOptions::default(), &TypeContext::default());
+            assert!(matches!(result, Err(errors::Error::TooManyRegisters(_, 121, _))));
+        }
+    }
+}
@@ -0,0 +1,311 @@
+pub mod output_node {
+    use proc_macro2::TokenStream;
+    use quote::{format_ident, quote, ToTokens};
+    use syn::{parse_quote, spanned::Spanned, GenericArgument, Ident, PathArguments, ReturnType, Type};
+    use crate::errors::errors::Error;
+    use crate::input_node::input_node::{generated_ident, match_type, EnumType, InputTypes};
+    use crate::type_context::type_context::{TypeContext, MAX_NESTING};
+
+    // the reply layout: the return value, one register per scalar of a tuple
+    // or struct, followed by the `&mut T` out-parameters in declaration order.
+    // a returned capability takes no register, it arrives in recv_slot
+    pub struct OutputParam {
+        value: Option<ReplyValue>,
+        out_params: Vec<(Ident, Type, InputTypes)>,
+        recv_slot: Option<Ident>,
+    }
+
+    // the shape of the returned value, rebuilt from its scalars on the client.
+    // scalars are encoded by their resolved type and named as written
+    enum ReplyValue {
+        Scalar(Type, Box<Type>, InputTypes),
+        Tuple(Vec<ReplyValue>),
+        Struct(Type, Vec<(Ident, ReplyValue)>),
+    }
+
+    // splits an IDL `-> Result<T, E>` into `-> T` and `E`
+    pub fn split_result(return_type: ReturnType) -> (ReturnType, Option<Type>) {
+        if let ReturnType::Type(arrow, ref ty) = return_type {
+            if let Type::Path(ref type_path) = **ty {
+                if let Some(seg) = type_path.path.segments.last() {
+                    if let (true, PathArguments::AngleBracketed(ref bracketed_args)) = (seg.ident == "Result", &seg.arguments) {
+                        let mut args = bracketed_args.args.iter();
+                        if let (Some(GenericArgument::Type(ok)), Some(GenericArgument::Type(err)), None) = (args.next(), args.next(), args.next()) {
+                            return (ReturnType::Type(arrow, Box::new(ok.clone())), Some(err.clone()));
+                        }
+                    }
+                }
+            }
+        }
+        (return_type, None)
+    }
+
+    // what the generated client methods return
+    pub fn wrap_result(return_type: &ReturnType, error_type: &Type) -> TokenStream {
+        match return_type {
+            ReturnType::Default => quote! { -> Result<(), #error_type> },
+            ReturnType::Type(_, ty) => quote! { -> Result<#ty, #error_type> },
+        }
+    }
+
+    // handles and capabilities are passed by reference, but returned by value
+    fn reply_type(ty: Type) -> Result<(Type, InputTypes), Error> {
+        let by_ref: Type = parse_quote! { &#ty };
+        match (match_type(ty.clone()), match_type(by_ref)) {
+            (Ok(output_type @ (InputTypes::NumberType | InputTypes::BoolType)), _) => Ok((ty, output_type)),
+            (_, Ok(output_type @ (InputTypes::LocalHandleType | InputTypes::AbsoluteCptrType))) => Ok((ty, output_type)),
+            _ => Err(Error::UnsupportedReturnType(ty.to_token_stream().to_string(), ty.span()))
+        }
+    }
+
+    fn is_cap(output_type: &InputTypes) -> bool {
+        matches!(output_type, InputTypes::AbsoluteCptrType)
+    }
+
+    // tuples and IDL structs are taken apart into their scalars. caps can't
+    // go in a struct, the server's cap and the client's slot differ in type
+    fn reply_value(ty: Type, context: &TypeContext, in_struct: bool, depth: usize) -> Result<ReplyValue, Error> {
+        let unsupported = || Error::UnsupportedReturnType(ty.to_token_stream().to_string(), ty.span());
+        if depth > MAX_NESTING {
+            return Err(unsupported());
+        }
+        let resolved = context.resolve(&ty);
+        if let Type::Tuple(ref tuple) = resolved {
+            // the elements as written, unless the tuple hides behind an alias
+            let elems = match ty {
+                Type::Tuple(ref written) => &written.elems,
+                _ => &tuple.elems
+            };
+            let elems = elems
+                .iter()
+                .map(|elem| reply_value(elem.clone(), context, in_struct, depth + 1))
+                .collect::<Result<Vec<_>, Error>>()?;
+            return Ok(ReplyValue::Tuple(elems));
+        }
+        if let Some(fields) = context.struct_fields(&resolved) {
+            let fields = fields
+                .into_iter()
+                .map(|(field, field_ty)| Ok((field, reply_value(field_ty, context, true, depth + 1)?)))
+                .collect::<Result<Vec<_>, Error>>()?;
+            return Ok(ReplyValue::Struct(ty, fields));
+        }
+        if let Some(idl_enum) = context.get_enum(&resolved) {
+            return Ok(ReplyValue::Scalar(resolved, Box::new(ty), EnumType::input_type(idl_enum)?));
+        }
+        if let Some(idl_flags) = context.get_flags(&resolved) {
+            return Ok(ReplyValue::Scalar(resolved, Box::new(ty), InputTypes::FlagsType(idl_flags.bit_width())));
+        }
+        match reply_type(resolved)? {
+            (_, InputTypes::AbsoluteCptrType) if in_struct => Err(unsupported()),
+            (resolved, output_type) => Ok(ReplyValue::Scalar(resolved, Box::new(ty), output_type))
+        }
+    }
+
+    impl ReplyValue {
+        fn scalars(&self) -> Vec<(&Type, &InputTypes)> {
+            match self {
+                ReplyValue::Scalar(ty, _, output_type) => vec![(ty, output_type)],
+                ReplyValue::Tuple(elems) => elems.iter().flat_map(|elem| elem.scalars()).collect(),
+                ReplyValue::Struct(_, fields) => fields.iter().flat_map(|(_, field)| field.scalars()).collect(),
+            }
+        }
+
+        fn handler_type(&self) -> TokenStream {
+            match self {
+                ReplyValue::Scalar(_, _, output_type) if is_cap(output_type) => quote! { sel4::cap::Unspecified },
+                ReplyValue::Scalar(_, written, _) => written.to_token_stream(),
+                ReplyValue::Struct(ty, _) => ty.to_token_stream(),
+                ReplyValue::Tuple(elems) => {
+                    let elems = elems.iter().map(|elem| elem.handler_type());
+                    quote! { (#(#elems,)*) }
+                }
+            }
+        }
+
+        // server side, takes `ident` apart and writes its scalars. each level
+        // gets its own block, `a.b` and `a_b` would share a name otherwise
+        fn marshal(&self, ident: Ident, buffer_name: &Ident, msg_index: &mut usize) -> TokenStream {
+            match self {
+                ReplyValue::Scalar(_, _, output_type) if is_cap(output_type) => quote! {
+                    #buffer_name.caps_or_badges_mut()[0] = #ident.bits();
+                },
+                ReplyValue::Scalar(ty, _, output_type) => {
+                    output_type.get_input_type_impl().marshal_code(ident, ty.clone(), buffer_name.clone(), msg_index)
+                },
+                ReplyValue::Tuple(elems) => {
+                    let idents: Vec<Ident> = (0..elems.len()).map(|i| format_ident!("{}_{}", ident, i)).collect();
+                    let marshalls: Vec<TokenStream> = idents
+                        .iter()
+                        .zip(elems)
+                        .map(|(elem_ident, elem)| elem.marshal(elem_ident.clone(), buffer_name, msg_index))
+                        .collect();
+                    quote! {
+                        {
+                            let (#(#idents,)*) = #ident;
+                            #(#marshalls)*
+                        }
+                    }
+                },
+                ReplyValue::Struct(ty, fields) => {
+                    let names = fields.iter().map(|(field, _)| field);
+                    let idents: Vec<Ident> = fields.iter().map(|(field, _)| format_ident!("{}_{}", ident, field)).collect();
+                    let marshalls: Vec<TokenStream> = idents
+                        .iter()
+                        .zip(fields)
+                        .map(|(field_ident, (_, field))| field.marshal(field_ident.clone(), buffer_name, msg_index))
+                        .collect();
+                    quote! {
+                        {
+                            let #ty { #(#names: #idents),* } = #ident;
+                            #(#marshalls)*
+                        }
+                    }
+                }
+            }
+        }
+
+        // client side, an expression rebuilding the value from the reply
+        fn unmarshal(&self, buffer_name: &Ident, msg_index: &mut usize, recv_slot: &Option<Ident>) -> TokenStream {
+            match (self, recv_slot) {
+                (ReplyValue::Scalar(_, _, output_type), Some(recv_slot)) if is_cap(output_type) => quote! { *#recv_slot },
+                (ReplyValue::Scalar(ty, _, output_type), _) => {
+                    output_type.get_input_type_impl().unmarshal_code(ty.clone(), buffer_name.clone(), msg_index)
+                },
+                (ReplyValue::Tuple(elems), _) => {
+                    let elems = elems.iter().map(|elem| elem.unmarshal(buffer_name, msg_index, recv_slot));
+                    quote! { (#(#elems,)*) }
+                },
+                (ReplyValue::Struct(ty, fields), _) => {
+                    let fields = fields.iter().map(|(field, value)| {
+                        let value = value.unmarshal(buffer_name, msg_index, recv_slot);
+                        quote! { #field: #value }
+                    });
+                    quote! { #ty { #(#fields),* } }
+                }
+            }
+        }
+    }
+
+    // out_params are the `&mut T` parameters with T already unwrapped, and
+    // recv_slot the `&AbsoluteCPtr` parameter, if there is one
+    pub fn get_output_param(return_type: ReturnType, out_params: Vec<(Ident, Type)>, recv_slot: Option<Ident>, context: &TypeContext) -> Result<OutputParam, Error> {
+        let out_params = out_params
+            .into_iter()
+            .map(|(ident, ty)| reply_type(ty).map(|(ty, output_type)| (ident, ty, output_type)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let ty = match return_type {
+            ReturnType::Type(_, ty) if !matches!(*ty, Type::Tuple(ref tuple) if tuple.elems.is_empty()) => *ty,
+            _ => return Ok(OutputParam { value: None, out_params, recv_slot }),
+        };
+        let value = reply_value(ty.clone(), context, false, 0)?;
+        // there is only the one receive slot
+        if value.scalars().iter().filter(|(_, output_type)| is_cap(output_type)).count() > 1 {
+            return Err(Error::UnsupportedReturnType(ty.to_token_stream().to_string(), ty.span()));
+        }
+        Ok(OutputParam { value: Some(value), out_params, recv_slot })
+    }
+
+    impl OutputParam {
+        pub fn is_unit(&self) -> bool {
+            self.value.is_none()
+        }
+
+        pub fn returns_cap(&self) -> bool {
+            self.value
+                .as_ref()
+                .is_some_and(|value| value.scalars().iter().any(|(_, output_type)| is_cap(output_type)))
+        }
+
+        // return type of the server-side handler, which hands out a cap from
+        // its own cspace where the client gets back its receive slot
+        pub fn get_handler_type(&self) -> TokenStream {
+            match &self.value {
+                Some(value) => {
+                    let ty = value.handler_type();
+                    quote! { Result<#ty, smos_common::error::InvocationError> }
+                },
+                None => quote! { Result<(), smos_common::error::InvocationError> }
+            }
+        }
+
+        // the reply's MessageInfo, ready for build()
+        pub fn get_reply_info_code(&self, reply_len: usize) -> TokenStream {
+            let extra_caps = if self.returns_cap() {
+                quote! { .extra_caps(1) }
+            } else {
+                quote! {}
+            };
+            quote! {
+                sel4::MessageInfoBuilder::default()
+                    .length(#reply_len)
+                    #extra_caps
+            }
+        }
+
+        // client side, a returned cap has to have been transferred, not unwrapped
+        pub fn get_cap_check_code(&self) -> TokenStream {
+            if !self.returns_cap() {
+                return quote! {};
+            }
+            quote! {
+                if __idl_reply.extra_caps() != 1 || __idl_reply.caps_unwrapped() != 0 {
+                    return Err(smos_common::error::InvocationError::InvalidArguments.into());
+                }
+            }
+        }
+
+        // server side, writes the reply from `ident` and the out-parameters
+        pub fn get_marshal_code(&self, ident: String, ipc_buffer_name: String, msg_index: &mut usize) -> TokenStream {
+            let buffer_name = format_ident!("{}", ipc_buffer_name);
+            let mut marshalls = vec![];
+            if let Some(value) = &self.value {
+                marshalls.push(value.marshal(format_ident!("{}", ident), &buffer_name, msg_index));
+            }
+            for (out_ident, ty, output_type) in &self.out_params {
+                let value = generated_ident(format!("{}_value", out_ident));
+                let marshal = output_type.get_input_type_impl().marshal_code(value.clone(), ty.clone(), buffer_name.clone(), msg_index);
+                marshalls.push(quote! {
+                    let #value = *#out_ident;
+                    #marshal
+                });
+            }
+            quote! { #(#marshalls)* }
+        }
+
+        // client side, an expression decoding the reply value, starting at msg_index
+        pub fn get_unmarshal_code(&self, ipc_buffer_name: String, msg_index: &mut usize) -> TokenStream {
+            let buffer_name = format_ident!("{}", ipc_buffer_name);
+            match &self.value {
+                Some(value) => value.unmarshal(&buffer_name, msg_index, &self.recv_slot),
+                None => quote! { () }
+            }
+        }
+
+        // registers taken by the reply value, the out-parameters follow
+        fn value_register_count(&self) -> usize {
+            self.value
+                .iter()
+                .flat_map(|value| value.scalars())
+                .map(|(_, output_type)| output_type.get_input_type_impl().register_count())
+                .sum()
+        }
+
+        pub fn register_count(&self) -> usize {
+            let out_params: usize = self.out_params
+                .iter()
+                .map(|(_, _, output_type)| output_type.get_input_type_impl().register_count())
+                .sum();
+            self.value_register_count() + out_params
+        }
+
+        pub fn get_out_param_code(&self, ipc_buffer_name: String) -> TokenStream {
+            let buffer_name = format_ident!("{}", ipc_buffer_name);
+            let mut msg_index = self.value_register_count();
+            let assignments = self.out_params.iter().map(|(ident, ty, output_type)| {
+                let decode = output_type.get_input_type_impl().unmarshal_code(ty.clone(), buffer_name.clone(), &mut msg_index);
+                quote! { *#ident = #decode; }
+            });
+            quote! { #(#assignments)* }
+        }
+    }
+}
@@ -0,0 +1,373 @@
+pub mod trait_node {
+    use proc_macro2::{Span, TokenStream};
+    use quote::{format_ident, quote, ToTokens};
+    use syn::{ext::IdentExt, parse_quote, punctuated::Punctuated, spanned::Spanned, token::Plus, Attribute, Expr, ExprLit, Ident, ItemTrait, Lit, LitStr, Meta, TraitItem, TraitItemFn, Type, TypeParamBound, Visibility};
+    use crate::errors::errors::Error;
+    use crate::input_node::input_node::generated_ident;
+    use crate::method_node::method_node::{is_idl_attr, MethodNode};
+    use crate::type_context::type_context::TypeContext;
+
+    // trait level `#[idl(...)]` settings
+    #[derive(Default)]
+    pub struct IdlOptions {
+        // connection type to implement the interface for
+        pub client: Option<Type>,
+        // error type of the client methods, InvocationError by default
+        pub error: Option<Type>,
+        // bit-pack small scalars into shared registers
+        pub pack: bool,
+        // error out instead of spilling to the shared buffer when registers run out
+        pub strict: bool,
+    }
+
+    impl IdlOptions {
+        pub fn error_type(&self) -> Type {
+            self.error.clone().unwrap_or(parse_quote! { smos_common::error::InvocationError })
+        }
+
+        fn new(attrs: &[Attribute]) -> Result<Self, Error> {
+            let mut options = IdlOptions::default();
+            for attr in attrs.iter().filter(|a| a.path().is_ident("idl")) {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("client") {
+                        let client: LitStr = meta.value()?.parse()?;
+                        options.client = Some(client.parse()?);
+                        Ok(())
+                    } else if meta.path.is_ident("pack") {
+                        options.pack = true;
+                        Ok(())
+                    } else if meta.path.is_ident("strict") {
+                        options.strict = true;
+                        Ok(())
+                    } else if meta.path.is_ident("error") {
+                        let error: LitStr = meta.value()?.parse()?;
+                        options.error = Some(error.parse()?);
+                        Ok(())
+                    } else {
+                        Err(meta.error("unknown idl option"))
+                    }
+                }).map_err(Error::Parse)?;
+            }
+            Ok(options)
+        }
+    }
+
+    // a message that can't be laid out, or a handle type the build ruled out, is never worth a stub
+    fn is_hard_error(e: &Error) -> bool {
+        match e {
+            Error::TooManyRegisters(..) | Error::TooManyCaps(..) | Error::UnknownHandleType(..) => true,
+            Error::Multiple(errors) => errors.iter().any(is_hard_error),
+            _ => false
+        }
+    }
+
+    // a module named `type` is spelt `r#type`, and `crate`, `self` and `super` can't even be raw
+    pub fn module_ident(name: &str) -> Ident {
+        match name {
+            "crate" | "self" | "super" => format_ident!("{}_", name),
+            _ => syn::parse_str(name).unwrap_or_else(|_| Ident::new_raw(name, Span::call_site())),
+        }
+    }
+
+    pub struct TraitNode {
+        attrs: Vec<Attribute>,
+        vis: Visibility,
+        ident: Ident,
+        supertraits: Punctuated<TypeParamBound, Plus>,
+        options: IdlOptions,
+        labels: Vec<(Ident, u64)>,
+        methods: Vec<(Ident, MethodNode)>,
+        unimplemented: Vec<(TraitItemFn, Error)>,
+    }
+
+    impl TraitNode {
+        // reports every trait level error at once, not just the first. errors
+        // are kept with the position of their item, so that once the trait
+        // fails the methods that would have been stubs are reported in order
+        pub fn new(item_trait: ItemTrait, context: &TypeContext) -> Result<Self, Error> {
+            let mut errors = vec![];
+            let options = IdlOptions::new(&item_trait.attrs).unwrap_or_else(|e| {
+                errors.push((0, e));
+                IdlOptions::default()
+            });
+            let mut labels: Vec<(Ident, u64)> = vec![];
+            let mut methods = vec![];
+            let mut unimplemented = vec![];
+            let mut next_label = 0;
+            for (position, item) in item_trait.items.iter().enumerate() {
+                match item {
+                    TraitItem::Fn(method) => {
+                        // a method without a usable label still has its params checked
+                        let variant = match Self::label(method, &labels, next_label) {
+                            Ok((variant, label)) => {
+                                next_label = label + 1;
+                                labels.push((variant.clone(), label));
+                                Some(variant)
+                            },
+                            Err(e) => {
+                                errors.push((position, e));
+                                None
+                            }
+                        };
+                        match (MethodNode::new(method.clone(), &options, context), variant) {
+                            (Ok(method_node), Some(variant)) => methods.push((variant, method_node)),
+                            (Ok(_), None) => {},
+                            (Err(e), _) if is_hard_error(&e) => errors.push((position, e)),
+                            (Err(e), _) => unimplemented.push((position, method.clone(), e))
+                        }
+                    },
+                    _ => {
+                        errors.push((position, Error::UnsupportedTraitItem(item.to_token_stream().to_string(), item.span())));
+                    }
+                }
+            }
+            if !errors.is_empty() {
+                errors.extend(unimplemented.drain(..).map(|(position, _, e)| (position, e)));
+                errors.sort_by_key(|(position, _)| *position);
+            }
+            if let Some(e) = Error::combine(errors.into_iter().map(|(_, e)| e).collect()) {
+                return Err(e);
+            }
+            let unimplemented = unimplemented.into_iter().map(|(_, method, e)| (method, e)).collect();
+            Ok(TraitNode {
+                attrs: item_trait.attrs.iter().filter(|a| !is_idl_attr(a)).cloned().collect(),
+                vis: item_trait.vis,
+                ident: item_trait.ident,
+                supertraits: item_trait.supertraits,
+                options,
+                labels,
+                methods,
+                unimplemented
+            })
+        }
+
+        pub fn ident(&self) -> &Ident {
+            &self.ident
+        }
+
+        // why each unsupported method fell back to an unimplemented stub
+        pub fn errors(&self) -> Vec<&Error> {
+            self.unimplemented.iter().map(|(_, e)| e).collect()
+        }
+
+        // the method's variant and label, unless either is already taken
+        fn label(method: &TraitItemFn, labels: &[(Ident, u64)], next_label: u64) -> Result<(Ident, u64), Error> {
+            let label = match Self::get_label(&method.attrs)? {
+                Some(label) => label,
+                None if next_label < crate::MAX_LABEL => next_label,
+                None => return Err(Error::NoNextLabel(method.sig.ident.to_string(), method.sig.ident.span())),
+            };
+            if labels.iter().any(|(_, l)| *l == label) {
+                return Err(Error::DuplicateLabel(method.sig.ident.to_string(), label, method.sig.ident.span()));
+            }
+            let variant = Self::label_variant(&method.sig.ident);
+            // get_x and getX both become GetX
+            if labels.iter().any(|(v, _)| *v == variant) {
+                return Err(Error::DuplicateVariant(method.sig.ident.to_string(), variant.to_string(), method.sig.ident.span()));
+            }
+            Ok((variant, label))
+        }
+
+        // reads an explicit `#[label = N]` off a method
+        fn get_label(attrs: &[Attribute]) -> Result<Option<u64>, Error> {
+            for attr in attrs {
+                if !attr.path().is_ident("label") {
+                    continue;
+                }
+                if let Meta::NameValue(ref name_value) = attr.meta {
+                    if let Expr::Lit(ExprLit { lit: Lit::Int(ref int), .. }) = name_value.value {
+                        // the kernel only carries the low bits of the label
+                        if let Ok(label @ 0..crate::MAX_LABEL) = int.base10_parse::<u64>() {
+                            return Ok(Some(label));
+                        }
+                    }
+                }
+                return Err(Error::InvalidLabel(attr.to_token_stream().to_string(), attr.span()));
+            }
+            Ok(None)
+        }
+
+        // add_window -> AddWindow, r#type -> Type
+        fn label_variant(method_ident: &Ident) -> Ident {
+            let camel: String = method_ident
+                .unraw()
+                .to_string()
+                .split('_')
+                .filter(|word| !word.is_empty())
+                .map(|word| {
+                    let mut chars = word.chars();
+                    match chars.next() {
+                        Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                        None => String::new()
+                    }
+                })
+                .collect();
+            format_ident!("{}", camel)
+        }
+
+        // UnifiedServerInterface -> unified_server_interface
+        pub fn module_ident(&self) -> Ident {
+            let mut snake = String::new();
+            for (i, c) in self.ident.to_string().chars().enumerate() {
+                if c.is_uppercase() {
+                    if i != 0 {
+                        snake.push('_');
+                    }
+                    snake.extend(c.to_lowercase());
+                } else {
+                    snake.push(c);
+                }
+            }
+            module_ident(&snake)
+        }
+
+        fn label_ident(&self) -> Ident {
+            format_ident!("{}Label", self.ident)
+        }
+
+        pub fn to_labels(&self) -> TokenStream {
+            let label_ident = self.label_ident();
+            let variants = self.labels.iter().map(|(variant, label)| quote! { #variant = #label });
+            let arms = self.labels.iter().map(|(variant, label)| quote! { #label => Ok(Self::#variant), });
+            quote! {
+                #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+                #[repr(u64)]
+                pub enum #label_ident {
+                    #(#variants),*
+                }
+
+                impl TryFrom<u64> for #label_ident {
+                    type Error = smos_common::error::InvocationError;
+
+                    fn try_from(label: u64) -> Result<Self, Self::Error> {
+                        match label {
+                            #(#arms)*
+                            _ => Err(smos_common::error::InvocationError::InvalidInvocation),
+                        }
+                    }
+                }
+            }
+        }
+
+        // the interface as callers see it, every method taking &self
+        // a mock can't be a connection, so with one the supertraits only
+        // bound the client impl, see to_client
+        pub fn to_trait(&self, mock: bool) -> TokenStream {
+            let trait_attrs = &self.attrs;
+            let trait_vis = &self.vis;
+            let trait_ident = &self.ident;
+            let supertraits = if self.supertraits.is_empty() || mock {
+                quote! {}
+            } else {
+                let supertraits = &self.supertraits;
+                quote! { : #supertraits }
+            };
+            let decls = self.methods.iter().map(|(_, m)| m.to_trait_decl());
+            let unimplemented = self.unimplemented
+                .iter()
+                .map(|(m, _)| MethodNode::to_unimplemented_decl(m.clone(), self.options.error_type()));
+            quote! {
+                #(#trait_attrs)*
+                #trait_vis trait #trait_ident #supertraits {
+                    #(#decls)*
+                    #(#unimplemented)*
+                }
+            }
+        }
+
+        // implements the interface for `#[idl(client = "...")]`, or for
+        // everything implementing the supertraits
+        pub fn to_client(&self) -> Result<TokenStream, Error> {
+            let label_ident = self.label_ident();
+            let trait_ident = &self.ident;
+            let impl_header = match (&self.options.client, self.supertraits.is_empty()) {
+                (Some(client), _) => quote! { impl #trait_ident for #client },
+                (None, false) => {
+                    let supertraits = &self.supertraits;
+                    quote! { impl<C: #supertraits> #trait_ident for C }
+                },
+                (None, true) => return Err(Error::NoClientType(self.ident.to_string(), self.ident.span()))
+            };
+            let methods = self.methods
+                .iter()
+                .map(|(variant, m)| m.to_method(generated_ident("ipc_buf").to_string(), generated_ident("msg").to_string(), quote! { #label_ident::#variant }));
+            let unimplemented = self.unimplemented
+                .iter()
+                .map(|(m, _)| MethodNode::to_unimplemented(m.clone(), self.options.error_type()));
+            Ok(quote! {
+                #impl_header {
+                    #(#methods)*
+                    #(#unimplemented)*
+                }
+            })
+        }
+
+        // a stand-in for a connection, implementing the interface with a
+        // closure per method. methods without one fail with InvalidInvocation
+        pub fn to_mock(&self) -> TokenStream {
+            let trait_ident = &self.ident;
+            let mock_ident = format_ident!("Mock{}", self.ident);
+            let error_type = self.options.error_type();
+            let fields = self.methods
+                .iter()
+                .map(|(_, m)| m.to_mock_field())
+                .chain(self.unimplemented.iter().map(|(m, _)| MethodNode::to_unimplemented_mock_field(m, error_type.clone())));
+            let methods = self.methods
+                .iter()
+                .map(|(_, m)| m.to_mock_method())
+                .chain(self.unimplemented.iter().map(|(m, _)| MethodNode::to_unimplemented_mock_method(m, error_type.clone())));
+            quote! {
+                #[derive(Default)]
+                pub struct #mock_ident {
+                    #(#fields)*
+                }
+
+                impl #trait_ident for #mock_ident {
+                    #(#methods)*
+                }
+            }
+        }
+
+        pub fn to_server(&self) -> TokenStream {
+            let label_ident = self.label_ident();
+            let handler_ident = format_ident!("{}Handler", self.ident);
+            let decls = self.methods.iter().map(|(_, m)| m.to_handler_decl());
+            let arms = self.methods
+                .iter()
+                .map(|(variant, m)| m.to_dispatch_arm(generated_ident("ipc_buf").to_string(), generated_ident("msg_info").to_string(), quote! { #label_ident::#variant }));
+            let shared_buffer_decl = if self.methods.iter().any(|(_, m)| m.uses_shared_buffer()) {
+                quote! {
+                    fn get_buf_mut(&self) -> Option<(*mut u8, usize)>;
+                }
+            } else {
+                quote! {}
+            };
+            // labels of unsupported methods still decode, but have no handler
+            let fallback_arm = if self.unimplemented.is_empty() {
+                quote! {}
+            } else {
+                quote! { _ => Err(smos_common::error::InvocationError::InvalidInvocation), }
+            };
+            quote! {
+                pub trait #handler_ident {
+                    #(#decls)*
+                    #shared_buffer_decl
+
+                    fn dispatch(&mut self, msg_info: sel4::MessageInfo, ipc_buf: &mut sel4::IpcBuffer) -> sel4::MessageInfo {
+                        match self.try_dispatch(msg_info, ipc_buf) {
+                            Ok(reply) => reply,
+                            Err(e) => e.fmt(ipc_buf),
+                        }
+                    }
+
+                    fn try_dispatch(&mut self, __idl_msg_info: sel4::MessageInfo, __idl_ipc_buf: &mut sel4::IpcBuffer) -> Result<sel4::MessageInfo, smos_common::error::InvocationError> {
+                        match #label_ident::try_from(__idl_msg_info.label())? {
+                            #(#arms)*
+                            #fallback_arm
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,519 @@
+pub mod type_context {
+    use proc_macro2::TokenStream;
+    use quote::{quote, ToTokens};
+    use std::collections::HashMap;
+    use syn::{braced, parse::{Parse, ParseStream}, punctuated::Punctuated, spanned::Spanned, token::Comma, Attribute, Expr, ExprLit, ExprUnary, Fields, GenericArgument, GenericParam, Ident, Index, ItemEnum, ItemStruct, ItemType, Lit, LitStr, Member, PathArguments, Token, Type, UnOp, Visibility};
+    use crate::errors::errors::Error;
+    use crate::input_node::input_node::handle_inner;
+
+    // paths starting with one of these name the types the generator knows
+    // by their last segment, e.g. core::num::NonZeroU32
+    const KNOWN_ROOTS: [&str; 8] = ["core", "std", "alloc", "crate", "self", "super", "sel4", "smos_common"];
+    // guards against `type A = B; type B = A;`
+    const MAX_ALIAS_DEPTH: usize = 32;
+    // guards against structs containing each other
+    pub const MAX_NESTING: usize = 32;
+
+    // an enum declared in the IDL file. variants are sent as a discriminant
+    // register, followed by the fields of data-carrying variants
+    #[derive(Clone)]
+    pub struct IdlEnum {
+        pub ident: Ident,
+        pub variants: Vec<IdlVariant>,
+    }
+
+    #[derive(Clone)]
+    pub struct IdlVariant {
+        pub ident: Ident,
+        // None if not given as an integer literal, or following one that isn't
+        pub discriminant: Option<i128>,
+        // tuple variants have `0`, `1`, ... as members, which work in braces too
+        pub fields: Vec<(Member, Type)>,
+    }
+
+    impl IdlEnum {
+        fn new(item_enum: &ItemEnum) -> Self {
+            let mut next = Some(0);
+            let variants = item_enum.variants
+                .iter()
+                .map(|variant| {
+                    let discriminant = match &variant.discriminant {
+                        Some((_, expr)) => int_literal(expr),
+                        None => next,
+                    };
+                    next = discriminant.map(|d| d + 1);
+                    let fields = variant.fields
+                        .iter()
+                        .enumerate()
+                        .map(|(i, field)| {
+                            let member = match &field.ident {
+                                Some(ident) => Member::Named(ident.clone()),
+                                None => Member::Unnamed(Index::from(i)),
+                            };
+                            (member, field.ty.clone())
+                        })
+                        .collect();
+                    IdlVariant { ident: variant.ident.clone(), discriminant, fields }
+                })
+                .collect();
+            IdlEnum { ident: item_enum.ident.clone(), variants }
+        }
+
+        // fieldless, so it converts with `as u64`
+        pub fn is_c_like(&self) -> bool {
+            self.variants.iter().all(|variant| variant.fields.is_empty())
+        }
+
+        // validates the discriminant coming from the other side
+        pub fn to_try_from(&self) -> TokenStream {
+            let ident = &self.ident;
+            let arms = self.variants.iter().map(|variant| {
+                let variant = &variant.ident;
+                quote! { value if value == #ident::#variant as u64 => Ok(#ident::#variant), }
+            });
+            quote! {
+                impl TryFrom<u64> for #ident {
+                    type Error = smos_common::error::InvocationError;
+
+                    fn try_from(value: u64) -> Result<Self, Self::Error> {
+                        match value {
+                            #(#arms)*
+                            _ => Err(smos_common::error::InvocationError::InvalidArguments),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // what the receiver does with bits no flag was declared for
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub enum UnknownBits {
+        // InvalidArguments, the default
+        Reject,
+        // silently dropped
+        Truncate,
+        // passed on as they are
+        Retain,
+    }
+
+    // `#[idl(flags)] struct Rights: u64 { READ = 1, WRITE = 2 }`, which isn't
+    // Rust, so it's parsed by hand and turned into a newtype over the bits.
+    // `#[idl(flags, unknown_bits = "truncate")]` picks the policy
+    #[derive(Clone)]
+    pub struct IdlFlags {
+        attrs: Vec<Attribute>,
+        vis: Visibility,
+        pub ident: Ident,
+        pub bits: Type,
+        flags: Vec<(Ident, Expr)>,
+        pub unknown_bits: UnknownBits,
+    }
+
+    // any attribute list containing `#[idl(flags)]` starts a flags type
+    pub fn is_flags(attrs: &[Attribute]) -> bool {
+        attrs.iter().filter(|a| a.path().is_ident("idl")).any(|attr| {
+            let mut flags = false;
+            let _ = attr.parse_nested_meta(|meta| {
+                flags |= meta.path.is_ident("flags");
+                if meta.input.peek(Token![=]) {
+                    meta.value()?.parse::<Expr>()?;
+                }
+                Ok(())
+            });
+            flags
+        })
+    }
+
+    impl Parse for IdlFlags {
+        fn parse(input: ParseStream) -> syn::Result<Self> {
+            let mut attrs = input.call(Attribute::parse_outer)?;
+            let mut unknown_bits = UnknownBits::Reject;
+            for attr in attrs.iter().filter(|a| a.path().is_ident("idl")) {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("flags") {
+                        Ok(())
+                    } else if meta.path.is_ident("unknown_bits") {
+                        let policy: LitStr = meta.value()?.parse()?;
+                        unknown_bits = match policy.value().as_str() {
+                            "reject" => UnknownBits::Reject,
+                            "truncate" => UnknownBits::Truncate,
+                            "retain" => UnknownBits::Retain,
+                            _ => return Err(syn::Error::new(policy.span(), "expected one of \"reject\", \"truncate\" or \"retain\"")),
+                        };
+                        Ok(())
+                    } else {
+                        Err(meta.error("unknown idl option"))
+                    }
+                })?;
+            }
+            attrs.retain(|a| !a.path().is_ident("idl"));
+            let vis = input.parse()?;
+            input.parse::<Token![struct]>()?;
+            let ident = input.parse()?;
+            input.parse::<Token![:]>()?;
+            let bits: Type = input.parse()?;
+            if !["u8", "u16", "u32", "u64", "usize"].contains(&bits.to_token_stream().to_string().as_str()) {
+                return Err(syn::Error::new(bits.span(), "flags have to be backed by an unsigned integer"));
+            }
+            let content;
+            braced!(content in input);
+            let flags = Punctuated::<(Ident, Expr), Comma>::parse_terminated_with(&content, |flag| {
+                let ident = flag.parse()?;
+                flag.parse::<Token![=]>()?;
+                Ok((ident, flag.parse()?))
+            })?;
+            Ok(IdlFlags { attrs, vis, ident, bits, flags: flags.into_iter().collect(), unknown_bits })
+        }
+    }
+
+    impl IdlFlags {
+        // the width of the bits when packed, so unknown ones aren't lost
+        pub fn bit_width(&self) -> Option<u32> {
+            match self.bits.to_token_stream().to_string().as_str() {
+                "u8" => Some(8),
+                "u16" => Some(16),
+                "u32" => Some(32),
+                _ => None,
+            }
+        }
+
+        // the newtype, its flags as associated consts, the usual set
+        // operations and the TryFrom<u64> applying the unknown bit policy
+        pub fn to_definition(&self) -> TokenStream {
+            let IdlFlags { attrs, vis, ident, bits, .. } = self;
+            let names = self.flags.iter().map(|(name, _)| name);
+            let values = self.flags.iter().map(|(_, value)| value);
+            let all = self.flags.iter().map(|(name, _)| quote! { #ident::#name.0 });
+            let decode = match self.unknown_bits {
+                UnknownBits::Reject => quote! {
+                    if value & !(Self::all().0 as u64) != 0 {
+                        return Err(smos_common::error::InvocationError::InvalidArguments);
+                    }
+                    Ok(Self(value as #bits))
+                },
+                UnknownBits::Truncate => quote! {
+                    Ok(Self((value & Self::all().0 as u64) as #bits))
+                },
+                UnknownBits::Retain => quote! {
+                    #bits::try_from(value).map(Self).map_err(|_| smos_common::error::InvocationError::InvalidArguments)
+                },
+            };
+            quote! {
+                #(#attrs)*
+                #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+                #vis struct #ident(#bits);
+
+                impl #ident {
+                    #(pub const #names: #ident = #ident(#values);)*
+
+                    pub const fn empty() -> Self {
+                        Self(0)
+                    }
+                    pub const fn all() -> Self {
+                        Self(0 #(| #all)*)
+                    }
+                    pub const fn bits(&self) -> #bits {
+                        self.0
+                    }
+                    // None if a bit is set that isn't a declared flag
+                    pub const fn from_bits(bits: #bits) -> Option<Self> {
+                        if bits & !Self::all().0 == 0 { Some(Self(bits)) } else { None }
+                    }
+                    pub const fn from_bits_truncate(bits: #bits) -> Self {
+                        Self(bits & Self::all().0)
+                    }
+                    pub const fn from_bits_retain(bits: #bits) -> Self {
+                        Self(bits)
+                    }
+                    pub const fn is_empty(&self) -> bool {
+                        self.0 == 0
+                    }
+                    pub const fn contains(&self, other: Self) -> bool {
+                        self.0 & other.0 == other.0
+                    }
+                    pub const fn intersects(&self, other: Self) -> bool {
+                        self.0 & other.0 != 0
+                    }
+                    pub fn insert(&mut self, other: Self) {
+                        self.0 |= other.0;
+                    }
+                    pub fn remove(&mut self, other: Self) {
+                        self.0 &= !other.0;
+                    }
+                }
+
+                impl core::ops::BitOr for #ident {
+                    type Output = Self;
+                    fn bitor(self, other: Self) -> Self {
+                        Self(self.0 | other.0)
+                    }
+                }
+
+                impl core::ops::BitAnd for #ident {
+                    type Output = Self;
+                    fn bitand(self, other: Self) -> Self {
+                        Self(self.0 & other.0)
+                    }
+                }
+
+                impl core::ops::BitOrAssign for #ident {
+                    fn bitor_assign(&mut self, other: Self) {
+                        self.0 |= other.0;
+                    }
+                }
+
+                impl core::ops::BitAndAssign for #ident {
+                    fn bitand_assign(&mut self, other: Self) {
+                        self.0 &= other.0;
+                    }
+                }
+
+                impl core::ops::Not for #ident {
+                    type Output = Self;
+                    fn not(self) -> Self {
+                        Self::from_bits_truncate(!self.0)
+                    }
+                }
+
+                impl TryFrom<u64> for #ident {
+                    type Error = smos_common::error::InvocationError;
+
+                    fn try_from(value: u64) -> Result<Self, Self::Error> {
+                        #decode
+                    }
+                }
+            }
+        }
+    }
+
+    // 3, -3
+    fn int_literal(expr: &Expr) -> Option<i128> {
+        match expr {
+            Expr::Lit(ExprLit { lit: Lit::Int(int), .. }) => int.base10_parse().ok(),
+            Expr::Unary(ExprUnary { op: UnOp::Neg(_), expr, .. }) => int_literal(expr).map(|value| -value),
+            _ => None
+        }
+    }
+
+    // what the generator knows about the types of the system it generates
+    // for, beyond what the IDL file itself says
+    #[derive(Default, Clone)]
+    pub struct TypeContext {
+        // the `T`s a `LocalHandle<T>` may name, any if not set
+        handle_types: Option<Vec<String>>,
+        // `type` aliases declared in the IDL file
+        aliases: HashMap<String, Type>,
+        // structs declared in the IDL file, with their fields in order
+        structs: HashMap<String, Vec<(Ident, Type)>>,
+        // enums declared in the IDL file
+        enums: HashMap<String, IdlEnum>,
+        // `#[idl(flags)]` types declared in the IDL file
+        flags: HashMap<String, IdlFlags>,
+    }
+
+    impl TypeContext {
+        pub fn new(handle_types: Option<Vec<String>>) -> Self {
+            TypeContext { handle_types, aliases: HashMap::new(), structs: HashMap::new(), enums: HashMap::new(), flags: HashMap::new() }
+        }
+
+        pub fn add_flags(&mut self, flags: &IdlFlags) {
+            self.flags.insert(flags.ident.to_string(), flags.clone());
+        }
+
+        pub fn get_flags(&self, ty: &Type) -> Option<IdlFlags> {
+            match ty {
+                Type::Path(type_path) if type_path.qself.is_none() => {
+                    self.flags.get(&type_path.path.get_ident()?.to_string()).cloned()
+                },
+                _ => None
+            }
+        }
+
+        pub fn add_enum(&mut self, item_enum: &ItemEnum) {
+            if item_enum.generics.params.is_empty() {
+                self.enums.insert(item_enum.ident.to_string(), IdlEnum::new(item_enum));
+            }
+        }
+
+        pub fn get_enum(&self, ty: &Type) -> Option<IdlEnum> {
+            match ty {
+                Type::Path(type_path) if type_path.qself.is_none() => {
+                    let ident = type_path.path.get_ident()?;
+                    let mut idl_enum = self.enums.get(&ident.to_string())?.clone();
+                    for variant in idl_enum.variants.iter_mut() {
+                        for (_, field_ty) in variant.fields.iter_mut() {
+                            *field_ty = self.resolve(field_ty);
+                        }
+                    }
+                    Some(idl_enum)
+                },
+                _ => None
+            }
+        }
+
+        // only plain structs with named fields can be sent, anything else is
+        // kept in the output but unknown to the generator
+        pub fn add_struct(&mut self, item_struct: &ItemStruct) {
+            if !item_struct.generics.params.is_empty() {
+                return;
+            }
+            if let Fields::Named(ref fields) = item_struct.fields {
+                let fields = fields.named
+                    .iter()
+                    .filter_map(|field| Some((field.ident.clone()?, field.ty.clone())))
+                    .collect();
+                self.structs.insert(item_struct.ident.to_string(), fields);
+            }
+        }
+
+        // the fields of a struct declared in the IDL file, typed as written
+        pub fn struct_fields(&self, ty: &Type) -> Option<Vec<(Ident, Type)>> {
+            let ident = match ty {
+                Type::Path(type_path) if type_path.qself.is_none() => type_path.path.get_ident()?,
+                _ => return None
+            };
+            self.structs.get(&ident.to_string()).cloned()
+        }
+
+        // aliases generic over types or consts are left alone, lifetimes are
+        // stripped on resolving anyway
+        pub fn add_alias(&mut self, alias: &ItemType) {
+            if alias.generics.params.iter().all(|param| matches!(param, GenericParam::Lifetime(_))) {
+                self.aliases.insert(alias.ident.to_string(), alias.ty.as_ref().clone());
+            }
+        }
+
+        // the form types are matched in: no lifetimes, no known path prefixes
+        // and no aliases. spans are kept so errors still point at the IDL
+        pub fn resolve(&self, ty: &Type) -> Type {
+            self.resolve_depth(ty, 0)
+        }
+
+        fn resolve_depth(&self, ty: &Type, depth: usize) -> Type {
+            let mut ty = ty.clone();
+            match &mut ty {
+                Type::Paren(paren) => return self.resolve_depth(&paren.elem, depth),
+                Type::Group(group) => return self.resolve_depth(&group.elem, depth),
+                Type::Reference(reference) => {
+                    reference.lifetime = None;
+                    *reference.elem = self.resolve_depth(&reference.elem, depth);
+                },
+                Type::Ptr(ptr) => *ptr.elem = self.resolve_depth(&ptr.elem, depth),
+                Type::Slice(slice) => *slice.elem = self.resolve_depth(&slice.elem, depth),
+                Type::Array(array) => *array.elem = self.resolve_depth(&array.elem, depth),
+                Type::Tuple(tuple) => {
+                    for elem in tuple.elems.iter_mut() {
+                        *elem = self.resolve_depth(elem, depth);
+                    }
+                },
+                Type::Path(type_path) if type_path.qself.is_none() => {
+                    let path = &mut type_path.path;
+                    let known_root = path.segments
+                        .first()
+                        .is_some_and(|seg| KNOWN_ROOTS.contains(&seg.ident.to_string().as_str()));
+                    if path.segments.len() > 1 && known_root {
+                        let last = path.segments.pop().map(|pair| pair.into_value());
+                        path.segments = last.into_iter().collect();
+                        path.leading_colon = None;
+                    }
+                    for seg in path.segments.iter_mut() {
+                        if let PathArguments::AngleBracketed(ref mut bracketed_args) = seg.arguments {
+                            bracketed_args.args = bracketed_args.args
+                                .iter()
+                                .filter(|arg| !matches!(arg, GenericArgument::Lifetime(_)))
+                                .map(|arg| match arg {
+                                    GenericArgument::Type(arg) => GenericArgument::Type(self.resolve_depth(arg, depth)),
+                                    arg => arg.clone()
+                                })
+                                .collect();
+                            if bracketed_args.args.is_empty() {
+                                seg.arguments = PathArguments::None;
+                            }
+                        }
+                    }
+                    if let Some(target) = path.get_ident().and_then(|ident| self.aliases.get(&ident.to_string())) {
+                        if depth < MAX_ALIAS_DEPTH {
+                            return self.resolve_depth(target, depth + 1);
+                        }
+                    }
+                },
+                _ => {}
+            }
+            ty
+        }
+
+        // every `LocalHandle<T>` in ty, however deeply nested, has to name
+        // one of the configured handle types
+        pub fn check_handles(&self, ty: &Type) -> Result<(), Error> {
+            let handle_types = match &self.handle_types {
+                Some(handle_types) => handle_types,
+                None => return Ok(())
+            };
+            if let Some(inner) = handle_inner(ty) {
+                let name = inner.to_token_stream().to_string();
+                if !handle_types.contains(&name) {
+                    return Err(Error::UnknownHandleType(name, inner.span()));
+                }
+                return Ok(());
+            }
+            match ty {
+                Type::Reference(reference) => self.check_handles(&reference.elem),
+                Type::Tuple(tuple) => tuple.elems.iter().try_for_each(|elem| self.check_handles(elem)),
+                Type::Path(type_path) => {
+                    for seg in &type_path.path.segments {
+                        if let PathArguments::AngleBracketed(ref bracketed_args) = seg.arguments {
+                            for arg in &bracketed_args.args {
+                                if let GenericArgument::Type(arg) = arg {
+                                    self.check_handles(arg)?;
+                                }
+                            }
+                        }
+                    }
+                    Ok(())
+                },
+                _ => Ok(())
+            }
+        }
+    }
+
+    // &'a str -> &str, generated signatures don't carry the IDL's generics
+    pub fn strip_lifetimes(ty: &Type) -> Type {
+        let mut ty = ty.clone();
+        match &mut ty {
+            Type::Reference(reference) => {
+                reference.lifetime = None;
+                *reference.elem = strip_lifetimes(&reference.elem);
+            },
+            Type::Paren(paren) => *paren.elem = strip_lifetimes(&paren.elem),
+            Type::Slice(slice) => *slice.elem = strip_lifetimes(&slice.elem),
+            Type::Array(array) => *array.elem = strip_lifetimes(&array.elem),
+            Type::Tuple(tuple) => {
+                for elem in tuple.elems.iter_mut() {
+                    *elem = strip_lifetimes(elem);
+                }
+            },
+            Type::Path(type_path) => {
+                for seg in type_path.path.segments.iter_mut() {
+                    if let PathArguments::AngleBracketed(ref mut bracketed_args) = seg.arguments {
+                        let args: Punctuated<GenericArgument, _> = bracketed_args.args
+                            .iter()
+                            .filter(|arg| !matches!(arg, GenericArgument::Lifetime(_)))
+                            .map(|arg| match arg {
+                                GenericArgument::Type(arg) => GenericArgument::Type(strip_lifetimes(arg)),
+                                arg => arg.clone()
+                            })
+                            .collect();
+                        if args.is_empty() {
+                            seg.arguments = PathArguments::None;
+                        } else {
+                            bracketed_args.args = args;
+                        }
+                    }
+                }
+            },
+            _ => {}
+        }
+        ty
+    }
+}
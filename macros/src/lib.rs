@@ -0,0 +1,60 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, punctuated::Punctuated, Ident, ItemTrait, Token};
+use smos_idl_core::trait_node::trait_node::TraitNode;
+use smos_idl_core::TypeContext;
+
+/// Expands an IDL trait in place. `#[interface]` generates the client side,
+/// `#[interface(server)]` the handler trait and dispatcher, and
+/// `#[interface(client, server)]` both. Re-exported as `smos_idl::interface`.
+#[proc_macro_attribute]
+pub fn interface(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr with Punctuated::<Ident, Token![,]>::parse_terminated);
+    let item_trait = parse_macro_input!(item as ItemTrait);
+
+    let mut client = args.is_empty();
+    let mut server = false;
+    for arg in &args {
+        match arg.to_string().as_str() {
+            "client" => client = true,
+            "server" => server = true,
+            _ => {
+                return syn::Error::new(arg.span(), "expected `client` or `server`")
+                    .to_compile_error()
+                    .into();
+            }
+        }
+    }
+
+    let trait_node = match TraitNode::new(item_trait, &TypeContext::default()) {
+        Ok(trait_node) => trait_node,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    // the trait is re-emitted with &self receivers and without IDL attributes
+    let trait_code = trait_node.to_trait(false);
+    // unsupported methods are hard errors here rather than unimplemented stubs
+    let errors: Vec<_> = trait_node.errors().iter().map(|e| e.to_compile_error()).collect();
+    if !errors.is_empty() {
+        return quote! {
+            #trait_code
+            #(#errors)*
+        }.into();
+    }
+
+    let labels = trait_node.to_labels();
+    let client_code = if client {
+        match trait_node.to_client() {
+            Ok(client_code) => client_code,
+            Err(e) => e.to_compile_error(),
+        }
+    } else {
+        quote! {}
+    };
+    let server_code = if server { trait_node.to_server() } else { quote! {} };
+    quote! {
+        #trait_code
+        #labels
+        #client_code
+        #server_code
+    }.into()
+}
@@ -1,6 +0,0 @@
-pub mod errors {
-    #[derive(Debug)]
-    pub enum Error {
-        InvalidArg(String),
-    }
-}
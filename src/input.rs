@@ -1,7 +1,10 @@
+use sel4::AbsoluteCPtr;
+use smos_common::local_handle::{ConnectionHandle, LocalHandle};
+
 pub trait UnifiedServerInterface: ClientConnection {
     fn add(a: usize, b: Option<bool>, c: usize, d: &AbsoluteCPtr) -> usize;
     fn set_slot(a: &str);
     fn handle(a: &LocalHandle<ConnectionHandle>);
     fn option(a: Option<&AbsoluteCPtr>);
     fn unimplement(a: foo);
-}
\ No newline at end of file
+}
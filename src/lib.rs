@@ -0,0 +1,4 @@
+// the generator lives in smos_idl_core so the attribute macro can build on
+// it too, this crate puts the two back together
+pub use smos_idl_core::*;
+pub use smos_idl_macros::interface;
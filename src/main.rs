@@ -1,68 +1,130 @@
-use proc_macro2::TokenStream;
-use quote::quote;
-use syn::{Item, ItemTrait, TraitItem, TraitItemFn};
-use std::fs::File as StdFile;
-use std::io::{Read, Write};
-mod method_node;
-use prettyplease;
-mod input_node;
-mod errors;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::exit;
+use smos_idl::{Builder, Verbosity};
 
-fn main() {
-    let input_path = "src/input.rs";
-    let mut file = StdFile::open(input_path).expect("Failed to open input file");
-    let mut content = String::new();
-    file.read_to_string(&mut content).expect("Failed to read in file");
-    let ast = syn::parse_file(&content).expect("Failed to parse content");
+const USAGE: &str = "\
+usage: smos_idl [options] <input>...
+
+Generates seL4 IPC stubs from one or more IDL files.
+With several inputs each file's code goes in a module named after it.
 
-    let mut implementations = vec![];
+options:
+    -o, --output <path>  write the generated code to <path>, or stdout for `-` (default)
+        --client         generate client stubs (default if no backend is chosen)
+        --server         generate the handler trait and dispatcher
+        --mock           generate a mock client for testing
+        --check          fail if <path> doesn't match the generated code
+        --handle-types <types>
+                         comma separated list of the only `T`s allowed in `LocalHandle<T>`
+    -q, --quiet          only print errors
+    -v, --verbose        also log every method encountered
+    -h, --help           print this message";
+
+struct Args {
+    inputs: Vec<PathBuf>,
+    output: Option<PathBuf>,
+    client: bool,
+    server: bool,
+    mock: bool,
+    check: bool,
+    verbosity: Verbosity,
+    handle_types: Option<Vec<String>>,
+}
 
-    for item in &ast.items {
-        match item {
-            Item::Trait(item_trait) => {
-                implementations = parse_trait(item_trait.clone());
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        inputs: vec![],
+        output: None,
+        client: false,
+        server: false,
+        mock: false,
+        check: false,
+        verbosity: Verbosity::Normal,
+        handle_types: None,
+    };
+    let mut argv = std::env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                let path = argv.next().ok_or(format!("{} expects a path", arg))?;
+                args.output = if path == "-" { None } else { Some(PathBuf::from(path)) };
             },
-            _ => {
-                
-            }
+            "--client" => args.client = true,
+            "--server" => args.server = true,
+            "--mock" => args.mock = true,
+            "--check" => args.check = true,
+            "--handle-types" => {
+                let types = argv.next().ok_or(format!("{} expects a list of types", arg))?;
+                args.handle_types = Some(types.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect());
+            },
+            "-q" | "--quiet" => args.verbosity = Verbosity::Quiet,
+            "-v" | "--verbose" => args.verbosity = Verbosity::Verbose,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0);
+            },
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ => args.inputs.push(PathBuf::from(arg)),
         }
     }
+    if args.inputs.is_empty() {
+        return Err(String::from("no input files"));
+    }
+    if args.check && args.output.is_none() {
+        return Err(String::from("--check needs an output file to compare against"));
+    }
+    if !(args.client || args.server || args.mock) {
+        args.client = true;
+    }
+    Ok(args)
+}
 
-    let output_code = quote! {
-        #(#implementations)*
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            exit(2);
+        }
     };
-    let formatted_code = format_rust_code(output_code.to_string());
+    let mut builder = Builder::new()
+        .client(args.client)
+        .server(args.server)
+        .mock(args.mock)
+        .verbosity(args.verbosity);
+    if let Some(handle_types) = args.handle_types {
+        builder = builder.handle_types(handle_types);
+    }
 
-    let mut file = StdFile::create("target/output.rs").expect("Failed to create output file");
-    writeln!(file, "{}", formatted_code).expect("Failed to write to output file");
-}
+    let code = match builder.generate_files(&args.inputs) {
+        Ok(code) => code,
+        Err(e) => {
+            eprint!("{}", e.report());
+            exit(1);
+        }
+    };
 
-fn parse_trait(item_trait: ItemTrait) -> Vec<TokenStream> {
-    println!("Parsing module {}", item_trait.ident.to_string());
-    let mut implementations = vec![];
-    item_trait.items.iter().for_each(|item| {
-        match item {
-            TraitItem::Fn(method) => {
-                println!("Method encountered {}", method.sig.ident.to_string());
-                implementations.push(parse_method(method.clone()));
-                println!("")
-            },
-            _ => {
-                unimplemented!()
+    match (&args.output, args.check) {
+        (Some(output), true) => {
+            let existing = fs::read_to_string(output).unwrap_or_default();
+            if existing != code {
+                eprintln!("error: {} is out of date, rerun smos_idl to regenerate it", output.display());
+                exit(1);
+            }
+        },
+        (Some(output), false) => {
+            if let Err(e) = fs::write(output, code) {
+                eprintln!("error: failed to write {}: {}", output.display(), e);
+                exit(1);
+            }
+        },
+        (None, _) => {
+            if let Err(e) = std::io::stdout().write_all(code.as_bytes()) {
+                eprintln!("error: failed to write to stdout: {}", e);
+                exit(1);
             }
         }
-    });
-
-    implementations
-}
-fn format_rust_code(code: String) -> String {
-    let syntax_tree: syn::File = syn::parse_str(&code).expect("Failed to parse TokenStream into syntax tree");
-    prettyplease::unparse(&syntax_tree)
-}
-
-fn parse_method(method: TraitItemFn) -> TokenStream {
-    match method_node::method_node::MethodNode::new(method.clone()) {
-        Ok(method_node) => method_node.to_method(String::from("ipc_buf"), String::from("msg"), 0),
-        Err(_) => method_node::method_node::MethodNode::to_unimplemented(method)
     }
-}
\ No newline at end of file
+}